@@ -28,6 +28,9 @@
 extern crate libc;
 
 use std::c_vec::CVec;
+use std::cmp;
+use std::io::{mod, IoError, IoResult};
+use std::slice::bytes;
 use libc::{c_void, size_t, c_int};
 
 #[link(name = "miniz", kind = "static")]
@@ -101,9 +104,70 @@ pub fn inflate_bytes_zlib(bytes: &[u8]) -> Option<CVec<u8>> {
     inflate_bytes_internal(bytes, TINFL_FLAG_PARSE_ZLIB_HEADER)
 }
 
+/// A `Reader` which inflates the deflate stream read from another `Reader`.
+///
+/// By default the stream is expected to be wrapped in a zlib header and
+/// checksum; `DeflateReader::new_raw` reads a bare deflate stream instead.
+/// Note that the whole of the underlying reader is read and inflated on the
+/// first call to `read`, so this is not suited to unbounded streams.
+pub struct DeflateReader<R> {
+    inner: R,
+    zlib: bool,
+    out: Option<CVec<u8>>,
+    pos: uint,
+}
+
+impl<R: Reader> DeflateReader<R> {
+    /// Creates a new `DeflateReader` inflating the zlib stream read from `r`.
+    pub fn new(r: R) -> DeflateReader<R> {
+        DeflateReader { inner: r, zlib: true, out: None, pos: 0 }
+    }
+
+    /// Creates a new `DeflateReader` inflating the raw deflate stream, with no
+    /// zlib header, read from `r`.
+    pub fn new_raw(r: R) -> DeflateReader<R> {
+        DeflateReader { inner: r, zlib: false, out: None, pos: 0 }
+    }
+
+    /// Consumes the `DeflateReader`, returning the underlying `Reader`.
+    pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Reader> Reader for DeflateReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.out.is_none() {
+            let input = try!(self.inner.read_to_end());
+            let out = if self.zlib {
+                inflate_bytes_zlib(input.as_slice())
+            } else {
+                inflate_bytes(input.as_slice())
+            };
+            match out {
+                Some(out) => self.out = Some(out),
+                None => return Err(IoError {
+                    kind: io::InvalidInput,
+                    desc: "invalid deflate stream",
+                    detail: None,
+                    os_error: None,
+                }),
+            }
+        }
+
+        let out = self.out.as_ref().unwrap().as_slice();
+        if self.pos == out.len() && buf.len() != 0 {
+            return Err(io::standard_error(io::EndOfFile));
+        }
+        let n = cmp::min(buf.len(), out.len() - self.pos);
+        bytes::copy_memory(buf, out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{inflate_bytes, deflate_bytes};
+    use super::{inflate_bytes, deflate_bytes, DeflateReader};
+    use std::io::{InvalidInput, MemReader};
     use std::rand;
     use std::rand::Rng;
 
@@ -139,4 +203,19 @@ mod tests {
         let inflated = inflate_bytes(deflated.as_slice()).expect("inflation failed");
         assert_eq!(inflated.as_slice(), bytes);
     }
+
+    #[test]
+    fn test_deflate_reader() {
+        let zlib = vec!(0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00,
+                        0x06, 0x2c, 0x02, 0x15);
+        let mut r = DeflateReader::new(MemReader::new(zlib));
+        assert_eq!(r.read_to_end().unwrap(), b"hello".to_vec());
+
+        let raw = vec!(0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00);
+        let mut r = DeflateReader::new_raw(MemReader::new(raw));
+        assert_eq!(r.read_to_end().unwrap(), b"hello".to_vec());
+
+        let mut r = DeflateReader::new(MemReader::new(vec!(1, 2, 3)));
+        assert_eq!(r.read(&mut [0, ..8]).unwrap_err().kind, InvalidInput);
+    }
 }
@@ -26,7 +26,8 @@ fn combine(seek: SeekStyle, cur: uint, end: uint, offset: i64) -> IoResult<u64>
         Err(IoError {
             kind: io::InvalidInput,
             desc: "invalid seek to a negative offset",
-            detail: None
+            detail: None,
+            os_error: None
         })
     } else {
         Ok((offset + pos) as u64)
@@ -710,7 +710,8 @@ pub mod writer {
             _ => Err(io::IoError {
                 kind: io::OtherIoError,
                 desc: "int too big",
-                detail: Some(format!("{}", n))
+                detail: Some(format!("{}", n)),
+                os_error: None
             })
         }
     }
@@ -723,7 +724,8 @@ pub mod writer {
         Err(io::IoError {
             kind: io::OtherIoError,
             desc: "int too big",
-            detail: Some(format!("{}", n))
+            detail: Some(format!("{}", n)),
+            os_error: None
         })
     }
 
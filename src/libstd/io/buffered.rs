@@ -75,6 +75,14 @@ impl<R: Reader> BufferedReader<R> {
         BufferedReader::with_capacity(DEFAULT_BUF_SIZE, inner)
     }
 
+    /// Returns the number of bytes which have been read from the underlying
+    /// reader but not yet consumed.
+    ///
+    /// These bytes are returned by the next call to `fill_buf` without
+    /// touching the underlying reader, and can be skipped over with
+    /// `consume`.
+    pub fn buffered(&self) -> uint { self.cap - self.pos }
+
     /// Gets a reference to the underlying reader.
     pub fn get_ref<'a>(&self) -> &R { &self.inner }
 
@@ -90,6 +98,17 @@ impl<R: Reader> BufferedReader<R> {
     /// Note that any leftover data in the internal buffer is lost.
     pub fn into_inner(self) -> R { self.inner }
 
+    /// Unwraps this `BufferedReader`, returning the underlying reader along
+    /// with the data which had been read from it but not yet consumed.
+    ///
+    /// This allows a protocol to hand the underlying reader over to another
+    /// layer mid-stream, such as when upgrading a connection to TLS, without
+    /// losing data which the buffer read ahead.
+    pub fn into_inner_with_buffer(self) -> (R, Vec<u8>) {
+        let buffered = self.buf[self.pos..self.cap].to_vec();
+        (self.inner, buffered)
+    }
+
     /// Deprecated, use into_inner() instead
     #[deprecated = "renamed to into_inner()"]
     pub fn unwrap(self) -> R { self.into_inner() }
@@ -149,7 +168,8 @@ impl<R: Reader> Reader for BufferedReader<R> {
 pub struct BufferedWriter<W> {
     inner: Option<W>,
     buf: Vec<u8>,
-    pos: uint
+    pos: uint,
+    flush_on_drop: bool,
 }
 
 impl<W: Writer> BufferedWriter<W> {
@@ -161,7 +181,8 @@ impl<W: Writer> BufferedWriter<W> {
         BufferedWriter {
             inner: Some(inner),
             buf: buf,
-            pos: 0
+            pos: 0,
+            flush_on_drop: true,
         }
     }
 
@@ -170,6 +191,16 @@ impl<W: Writer> BufferedWriter<W> {
         BufferedWriter::with_capacity(DEFAULT_BUF_SIZE, inner)
     }
 
+    /// Controls whether any buffered data is flushed when this writer is
+    /// dropped.
+    ///
+    /// This is enabled by default. When disabled, dropping the writer does not
+    /// touch the underlying writer at all, and any data still buffered is
+    /// discarded.
+    pub fn set_flush_on_drop(&mut self, on: bool) {
+        self.flush_on_drop = on;
+    }
+
     fn flush_buf(&mut self) -> IoResult<()> {
         if self.pos != 0 {
             let ret = self.inner.as_mut().unwrap().write(self.buf[..self.pos]);
@@ -228,7 +259,7 @@ impl<W: Writer> Writer for BufferedWriter<W> {
 #[unsafe_destructor]
 impl<W: Writer> Drop for BufferedWriter<W> {
     fn drop(&mut self) {
-        if self.inner.is_some() {
+        if self.inner.is_some() && self.flush_on_drop {
             // dtors should not panic, so we ignore a panicked flush
             let _ = self.flush_buf();
         }
@@ -375,6 +406,17 @@ impl<S: Stream> BufferedStream<S> {
         w.into_inner()
     }
 
+    /// Unwraps this `BufferedStream`, returning the underlying stream along
+    /// with the data in the read buffer which had not yet been consumed.
+    ///
+    /// As with `into_inner`, the write buffer is flushed first. See
+    /// `BufferedReader::into_inner_with_buffer`.
+    pub fn into_inner_with_buffer(self) -> (S, Vec<u8>) {
+        let (InternalBufferedWriter(w), buffered) =
+            self.inner.into_inner_with_buffer();
+        (w.into_inner(), buffered)
+    }
+
     /// Deprecated, use into_inner() instead
     #[deprecated = "renamed to into_inner()"]
     pub fn unwrap(self) -> S { self.into_inner() }
@@ -478,6 +520,25 @@ mod test {
         assert!(reader.read(&mut buf).is_err());
     }
 
+    #[test]
+    fn test_buffered_reader_buffered() {
+        let inner = MemReader::new(vec!(0, 1, 2, 3, 4, 5));
+        let mut reader = BufferedReader::with_capacity(4, inner);
+        assert_eq!(reader.buffered(), 0);
+
+        assert_eq!(reader.fill_buf().unwrap().len(), 4);
+        assert_eq!(reader.buffered(), 4);
+
+        reader.consume(3);
+        assert_eq!(reader.buffered(), 1);
+        assert_eq!(reader.read_byte(), Ok(3));
+        assert_eq!(reader.buffered(), 0);
+
+        let b: &[_] = &[4, 5];
+        assert_eq!(reader.fill_buf().unwrap(), b);
+        assert_eq!(reader.buffered(), 2);
+    }
+
     #[test]
     fn test_buffered_writer() {
         let inner = Vec::new();
@@ -533,6 +594,34 @@ mod test {
         assert_eq!(a, w[]);
     }
 
+    #[test]
+    fn test_buffered_writer_flush_on_drop() {
+        let (tx, rx) = channel();
+        {
+            let mut w = BufferedWriter::new(io::ChanWriter::new(tx.clone()));
+            w.write(&[0, 1]).unwrap();
+        }
+        assert_eq!(rx.try_recv(), Ok(vec!(0, 1)));
+
+        {
+            let mut w = BufferedWriter::new(io::ChanWriter::new(tx));
+            w.set_flush_on_drop(false);
+            w.write(&[2, 3]).unwrap();
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_buffered_reader_into_inner_with_buffer() {
+        let inner = MemReader::new(b"line\nrest of the data".to_vec());
+        let mut reader = BufferedReader::with_capacity(8, inner);
+        assert_eq!(reader.read_line().unwrap(), "line\n".to_string());
+
+        let (mut inner, buffered) = reader.into_inner_with_buffer();
+        assert_eq!(buffered, b"res".to_vec());
+        assert_eq!(inner.read_to_end().unwrap(), b"t of the data".to_vec());
+    }
+
     // This is just here to make sure that we don't infinite loop in the
     // newtype struct autoderef weirdness
     #[test]
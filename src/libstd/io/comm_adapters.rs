@@ -144,7 +144,8 @@ impl Writer for ChanWriter {
             io::IoError {
                 kind: io::BrokenPipe,
                 desc: "Pipe closed",
-                detail: None
+                detail: None,
+                os_error: None
             }
         })
     }
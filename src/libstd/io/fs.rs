@@ -146,7 +146,8 @@ impl File {
                 Err(IoError {
                     kind: InvalidInput,
                     desc: "is a directory",
-                    detail: None
+                    detail: None,
+                    os_error: None
                 })
             } else {
                 Ok(File {
@@ -388,7 +389,8 @@ pub fn copy(from: &Path, to: &Path) -> IoResult<()> {
         return update_err(Err(IoError {
             kind: io::MismatchedFileTypeForOperation,
             desc: "the source path is not an existing file",
-            detail: None
+            detail: None,
+            os_error: None
         }), from, to)
     }
 
@@ -226,6 +226,7 @@ pub use self::IoErrorKind::*;
 
 use char::Char;
 use clone::Clone;
+use cmp;
 use default::Default;
 use error::{FromError, Error};
 use fmt;
@@ -245,6 +246,7 @@ use slice::SliceExt;
 use str::StrPrelude;
 use str;
 use string::String;
+use u16;
 use uint;
 use unicode::char::UnicodeChar;
 use vec::Vec;
@@ -307,7 +309,11 @@ pub struct IoError {
     /// A human-readable description about the error
     pub desc: &'static str,
     /// Detailed information about this error, not always available
-    pub detail: Option<String>
+    pub detail: Option<String>,
+    /// The raw error code reported by the OS (`errno`, or the result of
+    /// `GetLastError` or `WSAGetLastError` on Windows), if this error came
+    /// from the OS
+    pub os_error: Option<i32>,
 }
 
 impl IoError {
@@ -339,7 +345,7 @@ impl IoError {
 impl fmt::Show for IoError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            IoError { kind: OtherIoError, desc: "unknown error", detail: Some(ref detail) } =>
+            IoError { kind: OtherIoError, desc: "unknown error", detail: Some(ref detail), .. } =>
                 write!(fmt, "{}", detail),
             IoError { detail: None, desc, .. } =>
                 write!(fmt, "{}", desc),
@@ -386,6 +392,10 @@ pub enum IoErrorKind {
     ConnectionReset,
     /// The connection was aborted (terminated) by the remote server.
     ConnectionAborted,
+    /// The network containing the remote host could not be reached.
+    NetworkUnreachable,
+    /// The remote host could not be reached.
+    HostUnreachable,
     /// The network operation failed because it was not connected yet.
     NotConnected,
     /// The operation failed because a pipe was closed.
@@ -462,6 +472,9 @@ impl<T> UpdateIoError<T> for IoResult<T> {
 
 static NO_PROGRESS_LIMIT: uint = 1000;
 
+// Size of the stack buffer which `Reader::skip` discards bytes into
+const SKIP_BUF_SIZE: uint = 512;
+
 /// A trait for objects which are byte-oriented streams. Readers are defined by
 /// one method, `read`. This function will block until data is available,
 /// filling in the provided buffer with any data read.
@@ -614,6 +627,48 @@ pub trait Reader {
         }
     }
 
+    /// Reads `magic.len()` bytes and checks that they are equal to `magic`,
+    /// such as the signature at the start of a file format.
+    ///
+    /// # Error
+    ///
+    /// Fails with the same conditions as `read_exact`. If the bytes read do not
+    /// match `magic`, an `InvalidInput` error is returned. Either way the bytes
+    /// which were read are consumed.
+    fn expect(&mut self, magic: &[u8]) -> IoResult<()> {
+        let bytes = try!(self.read_exact(magic.len()));
+        if bytes[] == magic {
+            Ok(())
+        } else {
+            Err(IoError {
+                kind: InvalidInput,
+                desc: "unexpected bytes in stream",
+                detail: Some(format!("expected {}, found {}", magic, bytes)),
+                os_error: None,
+            })
+        }
+    }
+
+    /// Reads and discards exactly `n` bytes.
+    ///
+    /// The bytes are read through a small buffer on the stack, so skipping
+    /// does not allocate no matter how large `n` is.
+    ///
+    /// # Error
+    ///
+    /// Fails with the same conditions as `read`. If EOF is reached before `n`
+    /// bytes have been skipped then an `EndOfFile` error is returned, and the
+    /// bytes which were skipped up to that point are lost.
+    fn skip(&mut self, n: u64) -> IoResult<()> {
+        let mut buf = [0u8, ..SKIP_BUF_SIZE];
+        let mut left = n;
+        while left > 0 {
+            let len = cmp::min(left, SKIP_BUF_SIZE as u64) as uint;
+            left -= try!(self.read_at_least(1, buf[mut ..len])) as u64;
+        }
+        Ok(())
+    }
+
     /// Reads all remaining bytes from the stream.
     ///
     /// # Error
@@ -651,6 +706,23 @@ pub trait Reader {
         })
     }
 
+    /// Reads a string which is prefixed by its length in bytes, as written by
+    /// `Writer::write_u16_string`. The length is a big-endian `u16`, and is
+    /// followed by that many bytes of UTF-8.
+    ///
+    /// # Error
+    ///
+    /// This function returns all of the same errors as `read_exact` with an
+    /// additional error if the bytes are not a valid sequence of UTF-8.
+    fn read_u16_string(&mut self) -> IoResult<String> {
+        let len = try!(self.read_be_u16());
+        let bytes = try!(self.read_exact(len as uint));
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(standard_error(InvalidInput)),
+        }
+    }
+
     // Byte conversion helpers
 
     /// Reads `n` little-endian unsigned integer bytes.
@@ -1078,6 +1150,26 @@ pub trait Writer {
         self.write_str(s).and_then(|()| self.write(&[b'\n']))
     }
 
+    /// Writes a string prefixed by its length in bytes as a big-endian `u16`,
+    /// to be read back with `Reader::read_u16_string`.
+    ///
+    /// # Error
+    ///
+    /// If the string is longer than 65535 bytes, an `InvalidInput` error is
+    /// returned and nothing is written.
+    fn write_u16_string(&mut self, s: &str) -> IoResult<()> {
+        if s.len() > u16::MAX as uint {
+            return Err(IoError {
+                kind: InvalidInput,
+                desc: "string too long for a u16 length prefix",
+                detail: Some(format!("{} bytes", s.len())),
+                os_error: None,
+            })
+        }
+        try!(self.write_be_u16(s.len() as u16));
+        self.write_str(s)
+    }
+
     /// Write a single char, encoded as UTF-8.
     #[inline]
     fn write_char(&mut self, c: char) -> IoResult<()> {
@@ -1498,6 +1590,43 @@ pub trait Buffer: Reader {
         Ok(res)
     }
 
+    /// Reads and discards bytes up to and including the next occurrence of
+    /// `byte`, returning how many bytes were discarded.
+    ///
+    /// At most `max` bytes are discarded.
+    ///
+    /// # Error
+    ///
+    /// If any I/O error is encountered, it is immediately returned. If EOF is
+    /// reached before `byte` is found then `EndOfFile` is returned, and if
+    /// `max` bytes are discarded without finding `byte` then an `InvalidInput`
+    /// error is returned. In all of these cases the bytes which were discarded
+    /// are lost.
+    fn skip_until(&mut self, byte: u8, max: u64) -> IoResult<u64> {
+        let mut skipped = 0;
+        loop {
+            if skipped == max {
+                return Err(IoError {
+                    kind: InvalidInput,
+                    desc: "delimiter not found within the limit",
+                    detail: None,
+                    os_error: None,
+                })
+            }
+            let (used, found) = {
+                let available = try!(self.fill_buf());
+                let len = cmp::min(available.len() as u64, max - skipped) as uint;
+                match available[..len].iter().position(|&b| b == byte) {
+                    Some(i) => (i + 1, true),
+                    None => (len, false),
+                }
+            };
+            self.consume(used);
+            skipped += used as u64;
+            if found { return Ok(skipped) }
+        }
+    }
+
     /// Reads the next utf8-encoded character from the underlying stream.
     ///
     /// # Error
@@ -1664,6 +1793,8 @@ pub fn standard_error(kind: IoErrorKind) -> IoError {
         ConnectionRefused => "connection refused",
         ConnectionReset => "connection reset",
         ConnectionAborted => "connection aborted",
+        NetworkUnreachable => "network unreachable",
+        HostUnreachable => "host unreachable",
         NotConnected => "not connected",
         BrokenPipe => "broken pipe",
         PathAlreadyExists => "file already exists",
@@ -1678,6 +1809,7 @@ pub fn standard_error(kind: IoErrorKind) -> IoError {
         kind: kind,
         desc: desc,
         detail: None,
+        os_error: None,
     }
 }
 
@@ -1925,7 +2057,7 @@ impl fmt::Show for FilePermission {
 #[cfg(test)]
 mod tests {
     use self::BadReaderBehavior::*;
-    use super::{IoResult, Reader, MemReader, NoProgress, InvalidInput};
+    use super::{IoResult, Reader, MemReader, NoProgress, InvalidInput, EndOfFile};
     use prelude::*;
     use uint;
 
@@ -2025,6 +2157,69 @@ mod tests {
         assert_eq!(r.push_at_least(5, 1, &mut buf).unwrap_err().kind, InvalidInput);
     }
 
+    #[test]
+    fn test_skip() {
+        let bytes = Vec::from_fn(20, |i| i as u8);
+        let mut r = MemReader::new(bytes.clone());
+        assert!(r.skip(10).is_ok());
+        assert_eq!(r.read_exact(10).unwrap(), bytes[10..].to_vec());
+        assert!(r.skip(0).is_ok());
+
+        let mut r = BadReader::new(MemReader::new(bytes),
+                                   vec![BadBehavior(1), GoodBehavior(uint::MAX)]);
+        assert!(r.skip(5).is_ok());
+        assert_eq!(r.read_byte(), Ok(5));
+        assert_eq!(r.skip(15).unwrap_err().kind, EndOfFile);
+    }
+
+    #[test]
+    fn test_u16_string() {
+        let mut w = Vec::new();
+        w.write_u16_string("hello").unwrap();
+        w.write_u16_string("").unwrap();
+        assert_eq!(w, b"\x00\x05hello\x00\x00".to_vec());
+
+        let mut r = MemReader::new(w);
+        assert_eq!(r.read_u16_string().unwrap(), "hello".to_string());
+        assert_eq!(r.read_u16_string().unwrap(), "".to_string());
+        assert_eq!(r.read_u16_string().unwrap_err().kind, EndOfFile);
+
+        let mut r = MemReader::new(b"\x00\x02\xc3\x28".to_vec());
+        assert_eq!(r.read_u16_string().unwrap_err().kind, InvalidInput);
+
+        let long = String::from_char(70000, 'a');
+        let mut w = Vec::new();
+        assert_eq!(w.write_u16_string(long[]).unwrap_err().kind, InvalidInput);
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn test_expect() {
+        let mut r = MemReader::new(b"RIFFdata".to_vec());
+        assert!(r.expect(b"RIFF").is_ok());
+        assert_eq!(r.read_to_end().unwrap(), b"data".to_vec());
+
+        let mut r = MemReader::new(b"GIF8data".to_vec());
+        assert_eq!(r.expect(b"RIFF").unwrap_err().kind, InvalidInput);
+
+        let mut r = MemReader::new(b"RI".to_vec());
+        assert_eq!(r.expect(b"RIFF").unwrap_err().kind, EndOfFile);
+    }
+
+    #[test]
+    fn test_skip_until() {
+        let mut r = MemReader::new(b"header\nbody".to_vec());
+        assert_eq!(r.skip_until(b'\n', 100), Ok(7));
+        assert_eq!(r.read_to_end().unwrap(), b"body".to_vec());
+
+        let mut r = MemReader::new(b"header\nbody".to_vec());
+        assert_eq!(r.skip_until(b'\n', 3).unwrap_err().kind, InvalidInput);
+        assert_eq!(r.read_byte(), Ok(b'd'));
+
+        let mut r = MemReader::new(b"header".to_vec());
+        assert_eq!(r.skip_until(b'\n', 100).unwrap_err().kind, EndOfFile);
+    }
+
     #[test]
     fn test_show() {
         use super::*;
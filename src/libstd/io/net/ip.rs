@@ -46,21 +46,53 @@ impl fmt::Show for IpAddr {
             Ipv4Addr(a, b, c, d) =>
                 write!(fmt, "{}.{}.{}.{}", a, b, c, d),
 
-            // Ipv4 Compatible address
-            Ipv6Addr(0, 0, 0, 0, 0, 0, g, h) => {
+            // Ipv4 Compatible address (other than `::` and `::1`, which are
+            // more familiar written as plain IPv6 addresses)
+            Ipv6Addr(0, 0, 0, 0, 0, 0, g, h) if g != 0 => {
                 write!(fmt, "::{}.{}.{}.{}", (g >> 8) as u8, g as u8,
                        (h >> 8) as u8, h as u8)
             }
 
             // Ipv4-Mapped address
             Ipv6Addr(0, 0, 0, 0, 0, 0xFFFF, g, h) => {
-                write!(fmt, "::FFFF:{}.{}.{}.{}", (g >> 8) as u8, g as u8,
+                write!(fmt, "::ffff:{}.{}.{}.{}", (g >> 8) as u8, g as u8,
                        (h >> 8) as u8, h as u8)
             }
 
-            Ipv6Addr(a, b, c, d, e, f, g, h) =>
-                write!(fmt, "{:x}:{:x}:{:x}:{:x}:{:x}:{:x}:{:x}:{:x}",
-                       a, b, c, d, e, f, g, h)
+            Ipv6Addr(a, b, c, d, e, f, g, h) => {
+                let groups = [a, b, c, d, e, f, g, h];
+
+                // As recommended by RFC 5952, the first of the longest runs
+                // of zero groups is elided, as long as it is at least two
+                // groups long.
+                let (mut start, mut len) = (0, 0);
+                let mut i = 0;
+                while i < groups.len() {
+                    let mut j = i;
+                    while j < groups.len() && groups[j] == 0 { j += 1 }
+                    if j - i > len {
+                        start = i;
+                        len = j - i;
+                    }
+                    i = j + 1;
+                }
+
+                fn fmt_groups(fmt: &mut fmt::Formatter, groups: &[u16]) -> fmt::Result {
+                    for (i, g) in groups.iter().enumerate() {
+                        if i > 0 { try!(write!(fmt, ":")) }
+                        try!(write!(fmt, "{:x}", *g));
+                    }
+                    Ok(())
+                }
+
+                if len >= 2 {
+                    try!(fmt_groups(fmt, groups[..start]));
+                    try!(write!(fmt, "::"));
+                    fmt_groups(fmt, groups[start + len..])
+                } else {
+                    fmt_groups(fmt, &groups)
+                }
+            }
         }
     }
 }
@@ -73,6 +105,28 @@ pub struct SocketAddr {
 
 impl Copy for SocketAddr {}
 
+impl SocketAddr {
+    /// Creates an IPv4 socket address from the four octets of the address
+    /// and a port.
+    ///
+    /// `SocketAddr::v4([127, 0, 0, 1], 80)` is the same as
+    /// `SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 80 }`.
+    #[experimental]
+    pub fn v4(octets: [u8, ..4], port: Port) -> SocketAddr {
+        SocketAddr {
+            ip: Ipv4Addr(octets[0], octets[1], octets[2], octets[3]),
+            port: port,
+        }
+    }
+
+    /// Creates a socket address for `port` on the IPv4 loopback address,
+    /// `127.0.0.1`.
+    #[experimental]
+    pub fn v4_loopback(port: Port) -> SocketAddr {
+        SocketAddr::v4([127, 0, 0, 1], port)
+    }
+}
+
 impl fmt::Show for SocketAddr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.ip {
@@ -442,7 +496,8 @@ pub trait ToSocketAddr {
             .and_then(|v| v.into_iter().next().ok_or_else(|| IoError {
                 kind: io::InvalidInput,
                 desc: "no address available",
-                detail: None
+                detail: None,
+                os_error: None
             }))
     }
 
@@ -472,6 +527,15 @@ impl ToSocketAddr for (IpAddr, u16) {
     }
 }
 
+// a list of addresses, which are tried in order by `TcpStream::connect` and
+// friends
+impl<'a> ToSocketAddr for &'a [SocketAddr] {
+    #[inline]
+    fn to_socket_addr_all(&self) -> IoResult<Vec<SocketAddr>> {
+        Ok(self.to_vec())
+    }
+}
+
 fn resolve_socket_addr(s: &str, p: u16) -> IoResult<Vec<SocketAddr>> {
     net::get_host_addresses(s)
         .map(|v| v.into_iter().map(|a| SocketAddr { ip: a, port: p }).collect())
@@ -485,7 +549,8 @@ fn parse_and_resolve_socket_addr(s: &str) -> IoResult<Vec<SocketAddr>> {
                 None => return Err(IoError {
                     kind: io::InvalidInput,
                     desc: $msg,
-                    detail: None
+                    detail: None,
+                    os_error: None
                 })
             }
         )
@@ -530,7 +595,8 @@ impl<'a> ToSocketAddr for &'a str {
                 .ok_or_else(|| IoError {
                     kind: io::InvalidInput,
                     desc: "no address available",
-                    detail: None
+                    detail: None,
+                    os_error: None
                 })
             )
     }
@@ -650,10 +716,60 @@ mod test {
     #[test]
     fn ipv6_addr_to_string() {
         let a1 = Ipv6Addr(0, 0, 0, 0, 0, 0xffff, 0xc000, 0x280);
-        assert!(a1.to_string() == "::ffff:192.0.2.128" ||
-                a1.to_string() == "::FFFF:192.0.2.128");
+        assert_eq!(a1.to_string(), "::ffff:192.0.2.128");
         assert_eq!(Ipv6Addr(8, 9, 10, 11, 12, 13, 14, 15).to_string(),
                    "8:9:a:b:c:d:e:f");
+
+        assert_eq!(Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 0).to_string(), "::");
+        assert_eq!(Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 1).to_string(), "::1");
+        assert_eq!(Ipv6Addr(0, 0, 0, 0, 0, 0, 0xc000, 0x221).to_string(),
+                   "::192.0.2.33");
+        assert_eq!(Ipv6Addr(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).to_string(),
+                   "2001:db8::1");
+        assert_eq!(Ipv6Addr(0x2001, 0, 0, 1, 0, 0, 0, 1).to_string(),
+                   "2001:0:0:1::1");
+        assert_eq!(Ipv6Addr(0x2001, 0xdb8, 0, 0, 1, 0, 0, 1).to_string(),
+                   "2001:db8::1:0:0:1");
+        // a single zero group is not elided
+        assert_eq!(Ipv6Addr(0x2001, 0xdb8, 0, 1, 1, 1, 1, 1).to_string(),
+                   "2001:db8:0:1:1:1:1:1");
+        assert_eq!(Ipv6Addr(1, 0, 0, 0, 0, 0, 0, 0).to_string(), "1::");
+    }
+
+    #[test]
+    fn ip_addr_eq() {
+        use collections::HashSet;
+
+        let a = Ipv4Addr(127, 0, 0, 1);
+        assert_eq!(a, Ipv4Addr(127, 0, 0, 1));
+        assert_eq!(a.clone(), a);
+        assert!(a != Ipv4Addr(127, 0, 0, 2));
+        assert!(a != Ipv6Addr(0, 0, 0, 0, 0, 0xffff, 0x7f00, 1));
+
+        let mut seen = HashSet::new();
+        assert!(seen.insert(a));
+        assert!(!seen.insert(Ipv4Addr(127, 0, 0, 1)));
+    }
+
+    #[test]
+    fn socket_addr_to_string() {
+        assert_eq!(SocketAddr::v4_loopback(8080).to_string(), "127.0.0.1:8080");
+        let a = SocketAddr { ip: Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 1), port: 80 };
+        assert_eq!(a.to_string(), "[::1]:80");
+
+        let addrs = [
+            SocketAddr { ip: Ipv4Addr(0, 0, 0, 0), port: 0 },
+            SocketAddr { ip: Ipv4Addr(10, 1, 255, 3), port: 65535 },
+            SocketAddr { ip: Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 0), port: 1 },
+            SocketAddr { ip: Ipv6Addr(0x2a02, 0x6b8, 0, 1, 0, 0, 0, 1), port: 53 },
+            SocketAddr { ip: Ipv6Addr(0, 0, 0, 0, 0, 0xffff, 0xc000, 0x280), port: 22 },
+            SocketAddr { ip: Ipv6Addr(0xfe80, 0, 0, 0, 0x1ff, 0xfe23, 0x4567, 0x890a),
+                         port: 443 },
+        ];
+        for &a in addrs.iter() {
+            let parsed: Option<SocketAddr> = FromStr::from_str(a.to_string()[]);
+            assert_eq!(parsed, Some(a));
+        }
     }
 
     #[test]
@@ -672,6 +788,18 @@ mod test {
         assert_eq!(Ok(vec![e]), (a, p).to_socket_addr_all());
     }
 
+    #[test]
+    fn to_socket_addr_slice() {
+        let a = SocketAddr { ip: Ipv4Addr(77, 88, 21, 11), port: 12345 };
+        let b = SocketAddr { ip: Ipv6Addr(0x2a02, 0x6b8, 0, 1, 0, 0, 0, 1), port: 53 };
+        let addrs = [a, b];
+        assert_eq!(Ok(a), addrs[].to_socket_addr());
+        assert_eq!(Ok(vec![a, b]), addrs[].to_socket_addr_all());
+
+        let none: &[SocketAddr] = &[];
+        assert_eq!(none.to_socket_addr().err().unwrap().kind, ::io::InvalidInput);
+    }
+
     #[test]
     fn to_socket_addr_str_u16() {
         let a = SocketAddr { ip: Ipv4Addr(77, 88, 21, 11), port: 24352 };
@@ -699,4 +827,12 @@ mod test {
         let a = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 23924 };
         assert!("localhost:23924".to_socket_addr_all().unwrap().contains(&a));
     }
+
+    #[test]
+    fn socket_addr_v4() {
+        let a = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 80 };
+        assert_eq!(SocketAddr::v4([127, 0, 0, 1], 80), a);
+        assert_eq!(SocketAddr::v4_loopback(80), a);
+        assert_eq!(SocketAddr::v4([10, 1, 2, 3], 8080).ip, Ipv4Addr(10, 1, 2, 3));
+    }
 }
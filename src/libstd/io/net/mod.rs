@@ -31,7 +31,8 @@ fn with_addresses<A, T, F>(addr: A, mut action: F) -> IoResult<T> where
     const DEFAULT_ERROR: IoError = IoError {
         kind: InvalidInput,
         desc: "no addresses found for hostname",
-        detail: None
+        detail: None,
+        os_error: None
     };
 
     let addresses = try!(addr.to_socket_addr_all());
@@ -77,6 +77,17 @@ impl UnixStream {
             .map(|inner| UnixStream { inner: inner })
     }
 
+    /// Connect to the socket named `name` in the abstract namespace.
+    ///
+    /// Abstract sockets are not represented in the filesystem, so nothing
+    /// needs to be cleaned up after them. This is only supported on Linux;
+    /// other platforms return an `IoUnavailable` error. See
+    /// `UnixListener::bind_abstract`.
+    #[experimental]
+    pub fn connect_abstract(name: &str) -> IoResult<UnixStream> {
+        UnixStreamImp::connect_abstract(name.as_bytes())
+            .map(|inner| UnixStream { inner: inner })
+    }
 
     /// Closes the reading half of this connection.
     ///
@@ -181,6 +192,19 @@ impl UnixListener {
         UnixListenerImp::bind(&path.to_c_str())
             .map(|inner| UnixListener { inner: inner })
     }
+
+    /// Creates a new listener on the socket named `name` in the abstract
+    /// namespace.
+    ///
+    /// Unlike a listener created with `bind`, this does not create a file, and
+    /// the name is released as soon as the listener is closed. Abstract
+    /// sockets are only supported on Linux; other platforms return an
+    /// `IoUnavailable` error.
+    #[experimental]
+    pub fn bind_abstract(name: &str) -> IoResult<UnixListener> {
+        UnixListenerImp::bind_abstract(name.as_bytes())
+            .map(|inner| UnixListener { inner: inner })
+    }
 }
 
 impl Listener<UnixStream, UnixAcceptor> for UnixListener {
@@ -307,6 +331,26 @@ mod tests {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn abstract_smoke() {
+        let path = next_test_unix();
+        let name = path.as_str().unwrap();
+        let mut acceptor = UnixListener::bind_abstract(name).listen();
+        assert!(!path.exists());
+
+        let name2 = name.to_string();
+        spawn(move|| {
+            let mut stream = UnixStream::connect_abstract(name2[]).unwrap();
+            stream.write(&[99]).unwrap();
+            assert_eq!(stream.read_byte(), Ok(100));
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.read_byte(), Ok(99));
+        stream.write(&[100]).unwrap();
+    }
+
     #[test]
     fn connect_error() {
         let path = if cfg!(windows) {
@@ -406,6 +450,24 @@ mod tests {
         assert!(path.exists());
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn bind_existing_path() {
+        use io::fs::unlink;
+
+        let path = next_test_unix();
+        let _acceptor = UnixListener::bind(&path).listen();
+        let err = UnixListener::bind(&path).err().unwrap();
+        assert_eq!(err.kind, PathAlreadyExists);
+        assert_eq!(err.os_error, Some(::libc::EADDRINUSE));
+        assert!(err.detail.is_some());
+
+        let path = next_test_unix();
+        File::create(&path).unwrap();
+        assert_eq!(UnixListener::bind(&path).err().unwrap().kind, PathAlreadyExists);
+        unlink(&path).unwrap();
+    }
+
     #[test]
     fn unix_clone_smoke() {
         let addr = next_test_unix();
@@ -17,22 +17,139 @@
 //! A TCP connection implements the `Reader` and `Writer` traits, while the TCP
 //! listener (socket server) implements the `Listener` and `Acceptor` traits.
 
+use any::{Any, AnyMutRefExt};
+use boxed::Box;
 use clone::Clone;
-use io::IoResult;
-use result::Result::Err;
-use io::net::ip::{SocketAddr, ToSocketAddr};
+use cmp;
+use collections::HashMap;
+use default::Default;
+use fmt;
+use io::{IoError, IoResult};
+use iter::{range, range_inclusive, IteratorExt};
+use result::Result::{Ok, Err};
+use io::net::ip::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddr};
 use io::{Reader, Writer, Listener, Acceptor};
-use io::{standard_error, TimedOut};
+use io::{standard_error, ConnectionRefused, EndOfFile, InvalidInput};
+use io::{ConnectionAborted, ConnectionReset, IoUnavailable};
+use io::{ResourceUnavailable, ShortWrite, TimedOut};
+use io::util::{BufferPool, PooledBuf};
 use option::Option;
 use option::Option::{None, Some};
+use ops::{Drop, Fn, FnMut, FnOnce};
+use os::MemoryMap;
+use slice::{mod, SliceExt, PartialEqSliceExt};
+use kinds::{Send, Sync};
+use mem;
+use str::{mod, FromStr, StrPrelude};
+use string::String;
+use sync::{Arc, Future, Mutex};
+use task::spawn;
+use thunk::Thunk;
 use time::Duration;
+use vec::Vec;
 
+use io::timer::sleep;
+use sys::timer;
 use sys::tcp::TcpStream as TcpStreamImp;
 use sys::tcp::TcpListener as TcpListenerImp;
 use sys::tcp::TcpAcceptor as TcpAcceptorImp;
 
 use sys_common;
 
+const DEFAULT_BACKLOG: uint = 128;
+const SELF_CONNECT_ATTEMPTS: uint = 3;
+const READ_TO_END_CHUNK: uint = 64 * 1024;
+
+/// A set of options which can be applied to a `TcpStream` all at once.
+///
+/// Each field corresponds to the `TcpStream` setter of the same name. The
+/// default value of this structure matches the options of a freshly created
+/// socket.
+#[deriving(Clone, PartialEq, Eq, Show)]
+#[experimental]
+pub struct SocketOptions {
+    /// See `TcpStream::set_nodelay`.
+    pub nodelay: bool,
+    /// See `TcpStream::set_keepalive`.
+    pub keepalive: Option<uint>,
+}
+
+impl Copy for SocketOptions {}
+
+/// The full set of keepalive settings of a `TcpStream`, applied with
+/// `TcpStream::set_keepalive_config`. All times are in seconds.
+#[deriving(Clone, PartialEq, Eq, Show)]
+#[experimental]
+pub struct KeepaliveConfig {
+    /// How long the connection must be idle before the first probe is sent.
+    /// This is the value set by `TcpStream::set_keepalive`, and is supported
+    /// on the same platforms.
+    pub idle: uint,
+    /// How long to wait between unanswered probes (`TCP_KEEPINTVL`).
+    /// Supported on Linux, Android, FreeBSD and DragonFly.
+    pub interval: uint,
+    /// How many unanswered probes are sent before the connection is dropped
+    /// (`TCP_KEEPCNT`). Supported on Linux, Android, FreeBSD and DragonFly.
+    pub count: uint,
+}
+
+impl Copy for KeepaliveConfig {}
+
+impl Default for SocketOptions {
+    fn default() -> SocketOptions {
+        SocketOptions { nodelay: false, keepalive: None }
+    }
+}
+
+/// Controls which of the addresses a host name resolves to are tried by
+/// `TcpStream::connect_with_policy`, and which local address is used.
+#[deriving(Clone, PartialEq, Eq, Show)]
+#[experimental]
+pub enum SourcePolicy {
+    /// Try the addresses in the order the resolver returned them.
+    Default,
+    /// Try IPv6 addresses before IPv4 addresses.
+    PreferIpv6,
+    /// Try IPv4 addresses before IPv6 addresses.
+    PreferIpv4,
+    /// Connect from the given local address, trying only the addresses of
+    /// the same family.
+    Bind(IpAddr),
+}
+
+impl Copy for SourcePolicy {}
+
+/// An IP address family, used by `TcpListener::bind_family` to select a
+/// wildcard address.
+#[deriving(Clone, PartialEq, Eq, Show)]
+#[experimental]
+pub enum AddressFamily {
+    /// IPv4, whose wildcard address is `0.0.0.0`.
+    V4,
+    /// IPv6, whose wildcard address is `::`.
+    V6,
+}
+
+impl Copy for AddressFamily {}
+
+/// The outcome of probing an address with `TcpStream::reachability`.
+#[deriving(Clone, PartialEq, Eq, Show)]
+#[experimental]
+pub enum Reachability {
+    /// A connection was established, so something is listening.
+    Open,
+    /// The connection was refused: the host is up, but nothing is listening
+    /// on the port.
+    Closed,
+    /// The host could not be reached, or the probe failed for some other
+    /// reason.
+    Unreachable,
+    /// No answer arrived before the timeout elapsed.
+    Timeout,
+}
+
+impl Copy for Reachability {}
+
 /// A structure which represents a TCP stream between a local socket and a
 /// remote socket.
 ///
@@ -55,11 +172,163 @@ use sys_common;
 /// ```
 pub struct TcpStream {
     inner: TcpStreamImp,
+    userdata: Option<Box<Any + Send>>,
+    byte_limit: Option<Arc<ByteLimit>>,
+    ip_slot: Option<Arc<IpSlot>>,
+    eof_seen: bool,
+    bytes_read: u64,
+    bytes_written: u64,
+    // Data held back by `set_cork` on platforms without `TCP_CORK`; `None`
+    // unless the stream is corked there.
+    corked: Option<Vec<u8>>,
+}
+
+// The largest amount of data `set_cork` holds back when it has to buffer the
+// data itself, roughly one full segment on an Ethernet link.
+const CORK_BUFFER_SIZE: uint = 1460;
+
+// The budget set by `TcpListener::set_total_byte_limit`, shared by a
+// listener's acceptors and every stream they accept.
+struct ByteLimit {
+    limit: u64,
+    used: Mutex<u64>,
+}
+
+impl ByteLimit {
+    fn exhausted(&self) -> bool { *self.used.lock() >= self.limit }
+    fn add(&self, n: uint) { *self.used.lock() += n as u64; }
+}
+
+// The limit set by `TcpListener::set_per_ip_limit`, along with the number of
+// live connections from each peer address, shared by a listener's acceptors.
+struct PerIpLimit {
+    max: uint,
+    active: Mutex<HashMap<IpAddr, uint>>,
+}
+
+// A connection counted against a `PerIpLimit`. It is shared by all clones of
+// the stream, and gives up its place once they have all been dropped.
+struct IpSlot {
+    limit: Arc<PerIpLimit>,
+    ip: IpAddr,
+}
+
+impl Drop for IpSlot {
+    fn drop(&mut self) {
+        let mut active = self.limit.active.lock();
+        let last = match active.get_mut(&self.ip) {
+            Some(n) => { *n -= 1; *n == 0 }
+            None => false,
+        };
+        if last {
+            active.remove(&self.ip);
+        }
+    }
+}
+
+// Counts `s` against `limit`, returning false if its peer already has the
+// maximum number of connections. Streams whose peer is unknown are admitted
+// without being counted.
+fn admit_peer(limit: &Arc<PerIpLimit>, s: &mut TcpStream) -> bool {
+    let ip = match s.peer_name() {
+        Ok(peer) => peer.ip,
+        Err(..) => return true,
+    };
+    let mut active = limit.active.lock();
+    let n = active.get(&ip).map_or(0, |n| *n);
+    if n >= limit.max { return false }
+    active.insert(ip, n + 1);
+    s.ip_slot = Some(Arc::new(IpSlot { limit: limit.clone(), ip: ip }));
+    true
 }
 
 impl TcpStream {
     fn new(s: TcpStreamImp) -> TcpStream {
-        TcpStream { inner: s }
+        TcpStream {
+            inner: s,
+            userdata: None,
+            byte_limit: None,
+            ip_slot: None,
+            eof_seen: false,
+            bytes_read: 0,
+            bytes_written: 0,
+            corked: None,
+        }
+    }
+
+    // Accounts for the outcome of a read from the connection.
+    fn note_read(&mut self, ret: IoResult<uint>) -> IoResult<uint> {
+        match ret {
+            Ok(n) => {
+                self.bytes_read += n as u64;
+                match self.byte_limit {
+                    Some(ref limit) => limit.add(n),
+                    None => {}
+                }
+            }
+            Err(ref e) if e.kind == EndOfFile => self.eof_seen = true,
+            Err(..) => {}
+        }
+        ret
+    }
+
+    // Accounts for the outcome of a write of `len` bytes to the connection,
+    // part of which may have been sent even if it failed.
+    fn note_write<T>(&mut self, len: uint, ret: IoResult<T>) -> IoResult<T> {
+        let n = match ret {
+            Ok(..) => len,
+            Err(IoError { kind: ShortWrite(n), .. }) => n,
+            Err(..) => 0,
+        };
+        self.bytes_written += n as u64;
+        match self.byte_limit {
+            Some(ref limit) => limit.add(n),
+            None => {}
+        }
+        ret
+    }
+
+    // Sends any data being held back by an emulated cork. Whatever could not
+    // be sent stays held, so that it still goes out ahead of later writes.
+    fn flush_corked(&mut self) -> IoResult<()> {
+        let held = match self.corked {
+            Some(ref mut held) if held.len() > 0 => {
+                mem::replace(held, Vec::new())
+            }
+            _ => return Ok(()),
+        };
+        let ret = self.inner.write(held[]);
+        let sent = match ret {
+            Ok(()) => held.len(),
+            Err(IoError { kind: ShortWrite(n), .. }) => n,
+            Err(..) => 0,
+        };
+        if sent < held.len() {
+            self.corked = Some(held[sent..].to_vec());
+        }
+        self.note_write(held.len(), ret)
+    }
+
+    // Writes `buf` through the buffer of an emulated cork. If sending the
+    // buffer fails, the part of `buf` which was not sent is taken back out of
+    // it, so that the error describes `buf` alone as for an ordinary write.
+    fn write_corked(&mut self, buf: &[u8]) -> IoResult<()> {
+        let full = {
+            let held = self.corked.as_mut().unwrap();
+            held.push_all(buf);
+            held.len() >= CORK_BUFFER_SIZE
+        };
+        if !full { return Ok(()) }
+        match self.flush_corked() {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let held = self.corked.as_mut().unwrap();
+                let unsent = cmp::min(buf.len(), held.len());
+                let len = held.len() - unsent;
+                held.truncate(len);
+                Err(partial_write(e, buf.len() - unsent))
+            }
+        }
     }
 
     /// Open a TCP connection to a remote host.
@@ -83,12 +352,15 @@ impl TcpStream {
     /// Same as the `connect` method, `addr` argument type can be anything which
     /// implements `ToSocketAddr` trait.
     ///
-    /// If a `timeout` with zero or negative duration is specified then
-    /// the function returns `Err`, with the error kind set to `TimedOut`.
+    /// A `timeout` of zero makes a single nonblocking attempt to connect,
+    /// which fails with `TimedOut` unless the connection could be established
+    /// straight away. If a negative duration is specified then the function
+    /// returns `Err`, with the error kind set to `TimedOut`, without trying to
+    /// connect at all.
     #[experimental = "the timeout argument may eventually change types"]
     pub fn connect_timeout<A: ToSocketAddr>(addr: A,
                                             timeout: Duration) -> IoResult<TcpStream> {
-        if timeout <= Duration::milliseconds(0) {
+        if timeout < Duration::milliseconds(0) {
             return Err(standard_error(TimedOut));
         }
 
@@ -98,6 +370,162 @@ impl TcpStream {
         })
     }
 
+    /// Opens a TCP connection to a remote host, choosing among its addresses
+    /// according to `policy`.
+    ///
+    /// This is the same as `connect`, except that the addresses which `addr`
+    /// resolves to are filtered and reordered as described by `policy`
+    /// before being tried in turn; see `SourcePolicy`.
+    #[experimental]
+    pub fn connect_with_policy<A: ToSocketAddr>(addr: A, policy: SourcePolicy)
+                                                -> IoResult<TcpStream> {
+        let addrs = try!(addr.to_socket_addr_all());
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for addr in addrs.iter() {
+            match addr.ip {
+                Ipv4Addr(..) => v4.push(*addr),
+                Ipv6Addr(..) => v6.push(*addr),
+            }
+        }
+        let (first, second, local) = match policy {
+            SourcePolicy::Default => (addrs, Vec::new(), None),
+            SourcePolicy::PreferIpv4 => (v4, v6, None),
+            SourcePolicy::PreferIpv6 => (v6, v4, None),
+            SourcePolicy::Bind(ip @ Ipv4Addr(..)) => (v4, Vec::new(), Some(ip)),
+            SourcePolicy::Bind(ip @ Ipv6Addr(..)) => (v6, Vec::new(), Some(ip)),
+        };
+
+        let mut err = IoError {
+            kind: InvalidInput,
+            desc: "no addresses found for hostname",
+            detail: None,
+            os_error: None,
+        };
+        for addr in first.iter().chain(second.iter()) {
+            match TcpStreamImp::connect_from(*addr, local, None) {
+                Ok(s) => return Ok(TcpStream::new(s)),
+                Err(e) => err = e,
+            }
+        }
+        Err(err)
+    }
+
+    /// Opens a TCP connection to a remote host, refusing to connect a socket
+    /// to itself.
+    ///
+    /// Connecting to an address on the local host on which nothing is
+    /// listening can, on some systems, result in the socket being given that
+    /// same address as its local address and connecting to itself (a TCP
+    /// "simultaneous open"). Such a connection is established but nothing is
+    /// on the other end, which can leave peer-to-peer code hanging. This is
+    /// the same as `connect`, except that self-connected sockets are detected
+    /// with `is_self_connect`, closed and the connection retried. If every
+    /// attempt results in a self-connect, an error of kind `ConnectionRefused`
+    /// is returned.
+    #[experimental]
+    pub fn connect_reject_self<A: ToSocketAddr>(addr: A) -> IoResult<TcpStream> {
+        let addrs = try!(addr.to_socket_addr_all());
+        let mut err = IoError {
+            kind: InvalidInput,
+            desc: "no addresses found for hostname",
+            detail: None,
+            os_error: None,
+        };
+        for addr in addrs.iter() {
+            for _ in range(0, SELF_CONNECT_ATTEMPTS) {
+                let mut stream = match TcpStream::connect(*addr) {
+                    Ok(stream) => stream,
+                    Err(e) => { err = e; break }
+                };
+                if !try!(stream.is_self_connect()) {
+                    return Ok(stream)
+                }
+                err = IoError {
+                    kind: ConnectionRefused,
+                    desc: "connection was made to the socket itself",
+                    detail: Some(format!("{}", addr)),
+                    os_error: None,
+                };
+            }
+        }
+        Err(err)
+    }
+
+    /// Returns whether this socket is connected to itself, that is whether its
+    /// local and peer addresses are the same. See `connect_reject_self`.
+    #[experimental]
+    pub fn is_self_connect(&mut self) -> IoResult<bool> {
+        let local = try!(self.socket_name());
+        let peer = try!(self.peer_name());
+        Ok(local == peer)
+    }
+
+    /// Opens a TCP connection to a remote host, configured with the same
+    /// options as `template`.
+    ///
+    /// This connects as `connect` does, and then applies the options returned
+    /// by `template.options()` to the new stream. This is useful to keep all
+    /// the connections of a pool uniformly configured.
+    #[experimental]
+    pub fn connect_like<A: ToSocketAddr>(addr: A, template: &mut TcpStream)
+                                         -> IoResult<TcpStream> {
+        let opts = try!(template.options());
+        let mut stream = try!(TcpStream::connect(addr));
+        try!(stream.set_options(&opts));
+        Ok(stream)
+    }
+
+    /// Starts opening a TCP connection to a remote host, returning immediately.
+    ///
+    /// The connection is established in the background, as `connect` would
+    /// establish it. The returned `ConnectFuture` can be used to retrieve the
+    /// stream once it is needed.
+    #[experimental]
+    pub fn connect_deferred<A: ToSocketAddr + Send>(addr: A) -> ConnectFuture {
+        ConnectFuture {
+            inner: Future::spawn(move|| TcpStream::connect(addr)),
+        }
+    }
+
+    /// Checks whether anything is accepting TCP connections at `addr`, giving
+    /// up after `timeout` has elapsed.
+    ///
+    /// On success the probing connection is immediately reset (via
+    /// `set_linger(Some(0))`) rather than shut down gracefully, so it does not
+    /// linger in `TIME_WAIT`. Returns whether the connection was established.
+    #[experimental = "the timeout argument may eventually change types"]
+    pub fn probe<A: ToSocketAddr>(addr: A, timeout: Duration) -> bool {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(mut stream) => {
+                let _ = stream.set_linger(Some(0));
+                true
+            }
+            Err(..) => false,
+        }
+    }
+
+    /// Probes `addr` as `probe` does, additionally telling apart the ways in
+    /// which the probe can fail.
+    ///
+    /// A refused connection means that the host is up but has nothing
+    /// listening on the port, and is reported as `Closed`, while a probe
+    /// which gets no answer within `timeout` is reported as `Timeout`. Any
+    /// other failure, including errors resolving `addr`, is reported as
+    /// `Unreachable`.
+    #[experimental = "the timeout argument may eventually change types"]
+    pub fn reachability<A: ToSocketAddr>(addr: A, timeout: Duration) -> Reachability {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(mut stream) => {
+                let _ = stream.set_linger(Some(0));
+                Reachability::Open
+            }
+            Err(ref e) if e.kind == ConnectionRefused => Reachability::Closed,
+            Err(ref e) if e.kind == TimedOut => Reachability::Timeout,
+            Err(..) => Reachability::Unreachable,
+        }
+    }
+
     /// Returns the socket address of the remote peer of this TCP connection.
     pub fn peer_name(&mut self) -> IoResult<SocketAddr> {
         self.inner.peer_name()
@@ -108,22 +536,492 @@ impl TcpStream {
         self.inner.socket_name()
     }
 
+    /// Reads bytes from this connection into `buf` without removing them
+    /// from the receive buffer, returning how many were copied.
+    ///
+    /// A later `read` will return the same bytes again. Like `read`, this
+    /// blocks until some data arrives, subject to the read timeout, and
+    /// returns an `EndOfFile` error if the peer has closed the connection.
+    #[experimental]
+    pub fn peek(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.inner.peek(buf)
+    }
+
+    /// Looks at the first bytes waiting to be read from this connection to
+    /// guess whether the peer is starting a TLS handshake, for servers which
+    /// accept both TLS and plaintext on the same port.
+    ///
+    /// This returns true if the data starts with a TLS handshake record: a
+    /// content type of `0x16`, followed by a protocol version of SSL 3.0 or
+    /// any version of TLS. The data is left in place, so it can still be read
+    /// in full by whichever protocol the connection is handed to. Only the
+    /// bytes which have already arrived are examined, and if just the first
+    /// one is available the guess is based on it alone.
+    ///
+    /// This blocks until some data arrives, subject to the read timeout. If
+    /// the peer closes the connection first, an `EndOfFile` error is
+    /// returned.
+    #[experimental]
+    pub fn detect_tls(&mut self) -> IoResult<bool> {
+        let mut buf = [0u8, ..3];
+        let n = try!(self.peek(&mut buf));
+        Ok(n >= 1 && buf[0] == 0x16 &&
+           (n < 2 || buf[1] == 3) &&
+           (n < 3 || buf[2] <= 4))
+    }
+
+    /// Returns true if a read from this handle has reported the end of the
+    /// stream, that is if the peer has closed its writing half.
+    ///
+    /// Nothing is read from the connection to answer this, so the end of the
+    /// stream can only be noticed by a `read` which fails with `EndOfFile`.
+    /// Each clone of a stream keeps track of this separately.
+    #[experimental]
+    pub fn eof(&self) -> bool {
+        self.eof_seen
+    }
+
+    /// Returns the number of bytes which have been read from this connection
+    /// through this handle.
+    ///
+    /// Only data which was actually received is counted, so failed reads add
+    /// nothing, and peeking does not count either. Each clone of a stream
+    /// keeps its own count, starting from zero.
+    #[experimental]
+    pub fn total_bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Returns the number of bytes which have been written to this connection
+    /// through this handle.
+    ///
+    /// As with `total_bytes_read`, only data which was actually sent is
+    /// counted; a write which fails with `ShortWrite(n)` adds `n`. Each clone
+    /// of a stream keeps its own count, starting from zero.
+    #[experimental]
+    pub fn total_bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
     /// Sets the nodelay flag on this connection to the boolean specified
     #[experimental]
     pub fn set_nodelay(&mut self, nodelay: bool) -> IoResult<()> {
         self.inner.set_nodelay(nodelay)
     }
 
+    /// Returns whether the nodelay flag is set on this connection.
+    #[experimental]
+    pub fn nodelay(&mut self) -> IoResult<bool> {
+        self.inner.nodelay()
+    }
+
+    /// Writes `buf` with the nodelay flag forced on, so that it is sent right
+    /// away, and then restores the previous setting of the flag.
+    ///
+    /// The setting is restored even if the write fails, in which case the
+    /// write's error is returned.
+    #[experimental]
+    pub fn flush_nodelay(&mut self, buf: &[u8]) -> IoResult<()> {
+        let prev = try!(self.nodelay());
+        try!(self.set_nodelay(true));
+        let ret = self.write(buf);
+        let restored = self.set_nodelay(prev);
+        ret.and(restored)
+    }
+
+    /// Sets whether this connection is corked.
+    ///
+    /// While a connection is corked, partial segments are held back rather
+    /// than sent straight away, so that several small writes (such as a
+    /// header followed by a body) go out in as few packets as possible. Held
+    /// data is sent once a full segment has accumulated, when the connection
+    /// is uncorked, or when it is dropped.
+    ///
+    /// This uses `TCP_CORK` on Linux and Android. Elsewhere, up to a segment
+    /// of data is buffered by this handle instead, which `flush` also sends;
+    /// clones of the stream are not corked there, and if the held data cannot
+    /// all be sent when uncorking, the rest stays held and the connection
+    /// stays corked. Uncorking a connection with nothing held back, or one
+    /// which was never corked, does nothing.
+    #[experimental]
+    pub fn set_cork(&mut self, on: bool) -> IoResult<()> {
+        match self.inner.set_cork(on) {
+            Err(ref e) if e.kind == IoUnavailable => {}
+            ret => return ret,
+        }
+        if on {
+            if self.corked.is_none() {
+                self.corked = Some(Vec::new());
+            }
+            Ok(())
+        } else {
+            try!(self.flush_corked());
+            self.corked = None;
+            Ok(())
+        }
+    }
+
+    /// Writes each of `bufs` in order, as if they were one contiguous buffer.
+    ///
+    /// This avoids copying the buffers together first, and where possible
+    /// hands them all to the OS at once with `writev`. Like `write`, this
+    /// only returns once everything has been written, and a write which times
+    /// out part way through fails with `ShortWrite`.
+    #[experimental]
+    pub fn writev(&mut self, bufs: &[&[u8]]) -> IoResult<()> {
+        try!(self.flush_corked().map_err(|e| partial_write(e, 0)));
+        let ret = self.inner.writev(bufs);
+        self.note_write(bufs.iter().fold(0, |n, buf| n + buf.len()), ret)
+    }
+
+    /// Reads data from this connection into `bufs`, filling each one in turn
+    /// before moving on to the next, and returns the total number of bytes
+    /// read.
+    ///
+    /// As with `read`, this returns as soon as some data is available, so
+    /// the buffers are not necessarily all filled. Data is read into several
+    /// of them at once with `readv` where possible, which is not the case
+    /// while a read timeout is set or the stream is nonblocking.
+    #[experimental]
+    pub fn readv(&mut self, bufs: &mut [&mut [u8]]) -> IoResult<uint> {
+        let ret = self.inner.readv(bufs);
+        self.note_read(ret)
+    }
+
+    /// Sets the time-to-live of the packets sent on this connection (the hop
+    /// limit, for IPv6), which must be between 1 and 255.
+    ///
+    /// Any other value results in an `InvalidInput` error, and leaves the
+    /// setting unchanged.
+    #[experimental]
+    pub fn set_ttl(&mut self, ttl: int) -> IoResult<()> {
+        try!(check_ttl(ttl));
+        self.inner.set_ttl(ttl)
+    }
+
+    /// Sets the linger timeout (`SO_LINGER`) of this connection.
+    ///
+    /// If the value specified is `Some(n)`, then closing this connection
+    /// blocks for up to `n` seconds while unsent data is delivered. A value
+    /// of `Some(0)` causes the connection to be reset when it is closed,
+    /// discarding any unsent data. `None` restores the default behavior of
+    /// closing the connection gracefully in the background.
+    #[experimental]
+    pub fn set_linger(&mut self, seconds: Option<uint>) -> IoResult<()> {
+        self.inner.set_linger(seconds)
+    }
+
+    /// Sets the receive low-water mark (`SO_RCVLOWAT`) of this connection.
+    ///
+    /// A blocking read will not complete until at least `bytes` bytes are
+    /// available to be read (or the connection is closed, or an error occurs),
+    /// even if the buffer given to `read` is larger. Reads which have a
+    /// timeout set, and reads smaller than `bytes`, may still return less.
+    /// Not all platforms support this option; Windows, for example, returns
+    /// an error.
+    #[experimental]
+    pub fn set_recv_lowat(&mut self, bytes: uint) -> IoResult<()> {
+        self.inner.set_recv_lowat(bytes)
+    }
+
+    /// Sets the size of the OS receive buffer (`SO_RCVBUF`) of this
+    /// connection to `bytes`.
+    ///
+    /// The OS may adjust the value; Linux, for example, doubles it to leave
+    /// room for bookkeeping. Use `recv_buffer_size` to find out the size
+    /// which was actually given.
+    #[experimental]
+    pub fn set_recv_buffer_size(&mut self, bytes: uint) -> IoResult<()> {
+        self.inner.set_recv_buffer_size(bytes)
+    }
+
+    /// Returns the size of the OS receive buffer of this connection.
+    #[experimental]
+    pub fn recv_buffer_size(&mut self) -> IoResult<uint> {
+        self.inner.recv_buffer_size()
+    }
+
+    /// Sets the size of the OS send buffer (`SO_SNDBUF`) of this connection
+    /// to `bytes`.
+    ///
+    /// As with `set_recv_buffer_size`, the OS may adjust the value, which can
+    /// be read back with `send_buffer_size`.
+    #[experimental]
+    pub fn set_send_buffer_size(&mut self, bytes: uint) -> IoResult<()> {
+        self.inner.set_send_buffer_size(bytes)
+    }
+
+    /// Returns the size of the OS send buffer of this connection.
+    #[experimental]
+    pub fn send_buffer_size(&mut self) -> IoResult<uint> {
+        self.inner.send_buffer_size()
+    }
+
     /// Sets the keepalive timeout to the timeout specified.
     ///
     /// If the value specified is `None`, then the keepalive flag is cleared on
     /// this connection. Otherwise, the keepalive timeout will be set to the
     /// specified time, in seconds.
+    ///
+    /// The idle time is only honored on Linux, Android, Mac OS X, iOS,
+    /// FreeBSD and DragonFly. Elsewhere keepalive is still turned on or off,
+    /// but the system's default idle time is used.
     #[experimental]
     pub fn set_keepalive(&mut self, delay_in_seconds: Option<uint>) -> IoResult<()> {
         self.inner.set_keepalive(delay_in_seconds)
     }
 
+    /// Returns the keepalive timeout of this connection, in seconds, or `None`
+    /// if keepalive is disabled.
+    ///
+    /// On platforms where the timeout cannot be queried, an `IoUnavailable`
+    /// error is returned if keepalive is enabled.
+    #[experimental]
+    pub fn keepalive(&mut self) -> IoResult<Option<uint>> {
+        self.inner.keepalive()
+    }
+
+    /// Enables keepalive on this connection with all of the settings in
+    /// `cfg`, including the probe interval and count which `set_keepalive`
+    /// leaves at the system defaults.
+    ///
+    /// See `KeepaliveConfig` for which settings each platform supports. On
+    /// platforms where the interval and count cannot be set, keepalive is
+    /// still enabled with the idle time of `cfg`, and an `IoUnavailable`
+    /// error is returned.
+    #[experimental]
+    pub fn set_keepalive_config(&mut self, cfg: KeepaliveConfig) -> IoResult<()> {
+        try!(self.set_keepalive(Some(cfg.idle)));
+        self.inner.set_keepalive_probes(cfg.interval, cfg.count)
+    }
+
+    /// Returns the keepalive settings of this connection, or `None` if
+    /// keepalive is disabled.
+    ///
+    /// On platforms where any of the settings cannot be queried, an
+    /// `IoUnavailable` error is returned if keepalive is enabled.
+    #[experimental]
+    pub fn keepalive_config(&mut self) -> IoResult<Option<KeepaliveConfig>> {
+        match try!(self.keepalive()) {
+            Some(idle) => {
+                let (interval, count) = try!(self.inner.keepalive_probes());
+                Ok(Some(KeepaliveConfig {
+                    idle: idle,
+                    interval: interval,
+                    count: count,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the current value of each option in `SocketOptions` for this
+    /// connection.
+    #[experimental]
+    pub fn options(&mut self) -> IoResult<SocketOptions> {
+        Ok(SocketOptions {
+            nodelay: try!(self.nodelay()),
+            keepalive: try!(self.keepalive()),
+        })
+    }
+
+    /// Applies every option in `opts` to this connection.
+    ///
+    /// This stops at, and returns, the first error encountered.
+    #[experimental]
+    pub fn set_options(&mut self, opts: &SocketOptions) -> IoResult<()> {
+        try!(self.set_nodelay(opts.nodelay));
+        self.set_keepalive(opts.keepalive)
+    }
+
+    /// Returns a human-readable summary of this connection's configuration.
+    ///
+    /// The summary includes the local and remote addresses, the nodelay and
+    /// keepalive settings, the time left (in milliseconds) before the read
+    /// and write timeouts expire, and the sizes of the kernel's receive and
+    /// send buffers. Any value which could not be queried is shown as `?`.
+    /// The exact format is unspecified and intended for logging only.
+    #[experimental]
+    pub fn describe(&mut self) -> String {
+        format!("TcpStream {{ local: {}, peer: {}, nodelay: {}, \
+                 keepalive: {}, read_timeout: {}, write_timeout: {}, \
+                 recv_buffer: {}, send_buffer: {} }}",
+                show(self.socket_name()),
+                show(self.peer_name()),
+                show(self.nodelay()),
+                show(self.keepalive()),
+                self.inner.read_timeout(),
+                self.inner.write_timeout(),
+                show(self.inner.recv_buffer_size()),
+                show(self.inner.send_buffer_size()))
+    }
+
+    /// Enables or disables kernel receive timestamps (`SO_TIMESTAMP`) on this
+    /// connection.
+    ///
+    /// Once enabled, `read_timestamped` reports when the kernel received the
+    /// data being read. This is currently only supported on Linux and
+    /// Android; other platforms return an `IoUnavailable` error.
+    #[experimental]
+    pub fn set_timestamping(&mut self, on: bool) -> IoResult<()> {
+        self.inner.set_timestamping(on)
+    }
+
+    /// Reads bytes into `buf`, like `read`, additionally returning the time at
+    /// which the kernel received them.
+    ///
+    /// The timestamp is expressed in nanoseconds since the Unix epoch, and is
+    /// `None` if the kernel did not provide one (for example, because
+    /// timestamping has not been enabled with `set_timestamping`).
+    #[experimental]
+    pub fn read_timestamped(&mut self, buf: &mut [u8])
+                            -> IoResult<(uint, Option<u64>)> {
+        let ret = self.inner.read_timestamped(buf);
+        let _ = self.note_read(ret.clone().map(|(n, _)| n));
+        ret
+    }
+
+    /// Returns the number of bytes written to this connection which the
+    /// peer has not yet acknowledged.
+    ///
+    /// This is currently only supported on Linux and Android; other platforms
+    /// return an `IoUnavailable` error.
+    #[experimental]
+    pub fn send_queue_len(&mut self) -> IoResult<uint> {
+        self.inner.send_queue_len()
+    }
+
+    /// Flushes this connection and waits up to `ms` milliseconds for the
+    /// peer to acknowledge everything written to it.
+    ///
+    /// Returns `true` if the send queue drained in time. This is best-effort:
+    /// an acknowledgment only means the peer's kernel received the data, not
+    /// that the peer application has read it. Since it relies on
+    /// `send_queue_len`, this always returns `false` on platforms where the
+    /// queue length is not available.
+    #[experimental]
+    pub fn flush_acked(&mut self, ms: u64) -> bool {
+        if self.flush().is_err() { return false }
+        let deadline = timer::now() + ms;
+        loop {
+            match self.send_queue_len() {
+                Ok(0) => return true,
+                Ok(..) => {}
+                Err(..) => return false,
+            }
+            if timer::now() >= deadline { return false }
+            sleep(Duration::milliseconds(1));
+        }
+    }
+
+    /// Returns the path MTU which the kernel has discovered for this
+    /// connection, in bytes.
+    ///
+    /// The value depends on the route to the peer and may change over the
+    /// lifetime of the connection. This is currently only supported on Linux
+    /// and Android; other platforms return an `IoUnavailable` error.
+    #[experimental]
+    pub fn path_mtu(&mut self) -> IoResult<uint> {
+        self.inner.path_mtu()
+    }
+
+    /// Attaches `data` to this handle of the stream, replacing any value which
+    /// was previously attached.
+    ///
+    /// This lets per-connection state (a session id, authentication status,
+    /// and so on) travel along with the stream itself. The data belongs to
+    /// this handle only: handles created with `clone` start out without any.
+    #[experimental]
+    pub fn set_userdata<T: Send + 'static>(&mut self, data: T) {
+        self.userdata = Some(box data as Box<Any + Send>);
+    }
+
+    /// Returns the data attached to this handle with `set_userdata`.
+    ///
+    /// If no data is attached, or the data is not of type `T`, `None` is
+    /// returned.
+    #[experimental]
+    pub fn userdata<T: 'static>(&mut self) -> Option<&mut T> {
+        match self.userdata {
+            Some(ref mut data) => {
+                let data: &mut Any = &mut **data;
+                data.downcast_mut::<T>()
+            }
+            None => None,
+        }
+    }
+
+    /// Reads bytes from this connection into `buf`, which may be
+    /// uninitialized.
+    ///
+    /// This behaves exactly as `read`, and additionally guarantees that the
+    /// contents of `buf` are only ever written to, never read. Callers may
+    /// therefore pass a buffer which has not been zeroed (for example the
+    /// spare capacity of a `Vec` after `set_len`), provided they only look at
+    /// the first `n` bytes, where `n` is the returned count.
+    #[experimental]
+    pub fn read_uninit(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let ret = self.inner.read(buf);
+        self.note_read(ret)
+    }
+
+    /// Reads from this connection into a buffer taken from `pool`.
+    ///
+    /// This is the same as calling `pool.read_from(self)`; see
+    /// `BufferPool::read_from` for details. The returned buffer goes back to
+    /// `pool` when it is dropped, so steady-state reads don't allocate.
+    #[experimental]
+    pub fn read_pooled<'a>(&mut self, pool: &'a mut BufferPool)
+                           -> IoResult<PooledBuf<'a>> {
+        pool.read_from(self)
+    }
+
+    /// Waits up to `ms` milliseconds for this connection to become readable,
+    /// without reading anything from it.
+    ///
+    /// Returns `true` if a read would not block. Note that this is also the
+    /// case when the peer has closed the connection or an error is pending, in
+    /// which case the read will return that condition rather than data.
+    #[experimental = "the timeout argument may change in type and value"]
+    pub fn poll_readable(&mut self, ms: u64) -> bool {
+        self.inner.poll_readable(ms)
+    }
+
+    /// Checks, without blocking or consuming any data, whether the peer of
+    /// this connection is still there.
+    ///
+    /// This peeks at the incoming side of the connection. If the peer has
+    /// reset the connection (or has closed its side of it) then this returns
+    /// `false`, otherwise the connection is presumed to be alive and `true`
+    /// is returned. Note that a peer which silently went away cannot be
+    /// detected this way until keepalive probes or a write reveal it.
+    ///
+    /// A reset found this way also runs the callback registered with
+    /// `on_disconnect`, if any.
+    #[experimental]
+    pub fn probe_alive(&mut self) -> bool {
+        self.inner.probe_alive()
+    }
+
+    /// Registers a callback to be invoked when this connection is found to
+    /// have been dropped.
+    ///
+    /// The callback is run at most once, on the task which first observes the
+    /// failure, with the error that was encountered. A connection is considered
+    /// dropped when an operation fails with `ConnectionReset`,
+    /// `ConnectionAborted` or `BrokenPipe`, or with a `TimedOut` error which
+    /// did not come from a timeout set on this stream (for example, when the
+    /// keepalive probes configured through `set_keepalive` go unanswered). If
+    /// the socket already has a pending asynchronous error when this is
+    /// called, the callback is invoked immediately.
+    ///
+    /// Registering a new callback replaces any previously registered one. Note
+    /// that the callback is shared among all cloned handles of this stream.
+    #[experimental]
+    pub fn on_disconnect<F>(&mut self, cb: F) where F: FnOnce(IoError) + Send {
+        self.inner.on_disconnect(Thunk::with_arg(cb))
+    }
+
     /// Closes the reading half of this connection.
     ///
     /// This method will close the reading portion of this connection, causing
@@ -169,6 +1067,21 @@ impl TcpStream {
         self.inner.close_write()
     }
 
+    /// Closes both halves of this connection, reporting any error in doing so.
+    ///
+    /// Afterwards, reads return `EndOfFile` and writes fail with an error.
+    /// Dropping a stream also closes it, but any error is ignored then. In
+    /// either case the underlying socket is only released once every handle
+    /// to it has been dropped, so a clone never operates on a descriptor
+    /// which may have been reused.
+    ///
+    /// Note that this method affects all cloned handles associated with this
+    /// stream, not just this one handle.
+    #[experimental]
+    pub fn close(&mut self) -> IoResult<()> {
+        self.inner.close()
+    }
+
     /// Sets a timeout, in milliseconds, for blocking operations on this stream.
     ///
     /// This function will set a timeout for all blocking operations (including
@@ -184,6 +1097,10 @@ impl TcpStream {
     /// this timeout will override any specific read or write timeouts
     /// previously set for this stream.
     ///
+    /// A timeout which has expired keeps failing operations until it is reset
+    /// or cleared, but it leaves the stream itself intact: once a new timeout
+    /// is set (or the timeout is cleared), operations proceed as normal.
+    ///
     /// For clarification on the semantics of interrupting a read and a write,
     /// take a look at `set_read_timeout` and `set_write_timeout`.
     #[experimental = "the timeout argument may change in type and value"]
@@ -208,13 +1125,57 @@ impl TcpStream {
         self.inner.set_read_timeout(timeout_ms)
     }
 
-    /// Sets the timeout for write operations on this stream.
+    /// Puts this handle to the stream in or out of nonblocking mode.
     ///
-    /// See documentation in `set_timeout` for the semantics of this write time.
-    /// This will overwrite any previous write timeout set through either this
-    /// function or `set_timeout`.
+    /// In nonblocking mode a read which finds no data waiting, or a write
+    /// which cannot hand any data to the OS, fails straight away with an
+    /// error of kind `ResourceUnavailable` instead of blocking. A write which
+    /// can only be partially completed fails with `ShortWrite`, carrying the
+    /// number of bytes which were written. Read and write timeouts have no
+    /// effect while the stream is nonblocking.
     ///
-    /// # Errors
+    /// The mode is carried over to clones of this handle made afterwards.
+    #[experimental]
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.inner.set_nonblocking(nonblocking)
+    }
+
+    /// Reads the remainder of this stream into `buf`, giving up after
+    /// `timeout_ms` milliseconds.
+    ///
+    /// The data is appended to `buf`, so that one vector can be reused
+    /// across many calls. Returns `true` if the end of the stream was reached,
+    /// or `false` if the timeout elapsed first, in which case `buf` holds
+    /// everything which had been received by then. Any other error is
+    /// returned as usual, again leaving the data read so far in `buf`.
+    ///
+    /// The read timeout of the stream is only changed for the duration of
+    /// the call.
+    #[experimental = "the timeout argument may change in type and value"]
+    pub fn read_to_end_into(&mut self, buf: &mut Vec<u8>, timeout_ms: u64)
+                            -> IoResult<bool> {
+        let saved = self.inner.read_timeout();
+        self.inner.set_read_timeout(Some(timeout_ms));
+        let ret;
+        loop {
+            match self.push_at_least(1, READ_TO_END_CHUNK, buf) {
+                Ok(..) => {}
+                Err(ref e) if e.kind == EndOfFile => { ret = Ok(true); break }
+                Err(ref e) if e.kind == TimedOut => { ret = Ok(false); break }
+                Err(e) => { ret = Err(e); break }
+            }
+        }
+        self.inner.set_read_timeout(saved);
+        ret
+    }
+
+    /// Sets the timeout for write operations on this stream.
+    ///
+    /// See documentation in `set_timeout` for the semantics of this write time.
+    /// This will overwrite any previous write timeout set through either this
+    /// function or `set_timeout`.
+    ///
+    /// # Errors
     ///
     /// When this timeout expires, if there is no pending write operation, no
     /// action is taken. Otherwise, the pending write operation will be
@@ -234,6 +1195,50 @@ impl TcpStream {
     pub fn set_write_timeout(&mut self, timeout_ms: Option<u64>) {
         self.inner.set_write_timeout(timeout_ms)
     }
+
+    /// Writes the contents of the memory mapped `region` to this stream.
+    ///
+    /// The data is sent straight from the mapping, without first being copied
+    /// into an intermediate buffer, which makes this suitable for serving
+    /// large files.
+    #[experimental]
+    pub fn write_mapped(&mut self, region: &MemoryMap) -> IoResult<()> {
+        let data = region.data() as *const u8;
+        let buf = unsafe { slice::from_raw_buf(&data, region.len()) };
+        self.write(buf)
+    }
+
+    /// Reads data from this stream into `buf`, giving up once `timeout_ms`
+    /// milliseconds have elapsed without any data arriving.
+    ///
+    /// Like `read`, this returns the number of bytes read as soon as any data
+    /// is available, even if that does not fill `buf`. Unlike
+    /// `set_read_timeout`, the deadline only applies to this one read, and it
+    /// takes effect even while the stream is nonblocking. If no data arrives
+    /// in time, an error of kind `TimedOut` is returned; a `timeout_ms` of 0
+    /// only reads data which has already arrived.
+    #[experimental = "the timeout argument may change in type and value"]
+    pub fn read_deadline(&mut self, buf: &mut [u8], timeout_ms: u64) -> IoResult<uint> {
+        let ret = self.inner.read_deadline(buf, timeout_ms);
+        self.note_read(ret)
+    }
+
+    /// Writes all of `buf` to this stream, giving up once `timeout_ms`
+    /// milliseconds have elapsed.
+    ///
+    /// Unlike `set_write_timeout`, this only applies to this one write, and it
+    /// reports how much was sent before the deadline passed. On success the
+    /// length of `buf` is returned. If only part of `buf` could be sent in
+    /// time, an error of kind `ShortWrite(n)` is returned, where `n` is the
+    /// number of bytes which were sent. If nothing at all could be sent, the
+    /// error is of kind `TimedOut`; in particular, a `timeout_ms` of 0 sends
+    /// nothing.
+    #[experimental = "the timeout argument may change in type and value"]
+    pub fn write_deadline(&mut self, buf: &[u8], timeout_ms: u64) -> IoResult<uint> {
+        try!(self.flush_corked().map_err(|e| partial_write(e, 0)));
+        let ret = self.inner.write_deadline(buf, timeout_ms);
+        self.note_write(buf.len(), ret)
+    }
 }
 
 impl Clone for TcpStream {
@@ -246,19 +1251,51 @@ impl Clone for TcpStream {
     /// Instead, the first read will receive the first packet received, and the
     /// second read will receive the second packet.
     fn clone(&self) -> TcpStream {
-        TcpStream { inner: self.inner.clone() }
+        TcpStream {
+            inner: self.inner.clone(),
+            userdata: None,
+            byte_limit: self.byte_limit.clone(),
+            ip_slot: self.ip_slot.clone(),
+            eof_seen: false,
+            bytes_read: 0,
+            bytes_written: 0,
+            corked: None,
+        }
     }
 }
 
 impl Reader for TcpStream {
     fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
-        self.inner.read(buf)
+        let ret = self.inner.read(buf);
+        self.note_read(ret).map_err(|e| {
+            add_detail(e, "read from", self.inner.peer_name())
+        })
     }
 }
 
 impl Writer for TcpStream {
     fn write(&mut self, buf: &[u8]) -> IoResult<()> {
-        self.inner.write(buf)
+        let ret = if self.corked.is_none() {
+            let ret = self.inner.write(buf);
+            self.note_write(buf.len(), ret)
+        } else {
+            self.write_corked(buf)
+        };
+        ret.map_err(|e| add_detail(e, "write to", self.inner.peer_name()))
+    }
+
+    /// TCP streams are not buffered, so there is usually nothing to flush:
+    /// every successful `write` has already handed its data to the OS. The
+    /// exception is a stream corked with `set_cork` on a platform without
+    /// `TCP_CORK`, whose held data is sent. Otherwise this always succeeds,
+    /// which allows a stream to be wrapped in buffering adapters which flush
+    /// their inner writer.
+    fn flush(&mut self) -> IoResult<()> { self.flush_corked() }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        let _ = self.flush_corked();
     }
 }
 
@@ -268,6 +1305,62 @@ impl sys_common::AsInner<TcpStreamImp> for TcpStream {
     }
 }
 
+impl sys_common::FromInner<TcpStreamImp> for TcpStream {
+    fn from_inner(inner: TcpStreamImp) -> TcpStream {
+        TcpStream::new(inner)
+    }
+}
+
+// Describes an error from sending data held by an emulated cork in terms of a
+// write of which `n` bytes of its own data were sent.
+fn partial_write(e: IoError, n: uint) -> IoError {
+    match e.kind {
+        ShortWrite(..) if n == 0 => standard_error(TimedOut),
+        ShortWrite(..) => IoError { kind: ShortWrite(n), ..e },
+        _ => e,
+    }
+}
+
+// Notes the failed operation and the address it was on in the detail of an
+// error, after any detail it already has, unless the address is unknown.
+fn add_detail(mut e: IoError, op: &str, addr: IoResult<SocketAddr>) -> IoError {
+    match addr {
+        Ok(addr) if e.kind != EndOfFile => {
+            let detail = match e.detail {
+                Some(ref d) => format!("{}; {} {}", d, op, addr),
+                None => format!("{} {}", op, addr),
+            };
+            e.detail = Some(detail);
+        }
+        _ => {}
+    }
+    e
+}
+
+// Formats a queried option for `TcpStream::describe`.
+fn show<T: fmt::Show>(r: IoResult<T>) -> String {
+    match r {
+        Ok(t) => format!("{}", t),
+        Err(..) => format!("?"),
+    }
+}
+
+/// A handle to a TCP connection which is being established in the background.
+///
+/// This is created by `TcpStream::connect_deferred`.
+#[experimental]
+pub struct ConnectFuture {
+    inner: Future<IoResult<TcpStream>>,
+}
+
+impl ConnectFuture {
+    /// Waits for the connection to be established, returning the connected
+    /// stream or the error which prevented the connection.
+    pub fn get(self) -> IoResult<TcpStream> {
+        self.inner.into_inner()
+    }
+}
+
 /// A structure representing a socket server. This listener is used to create a
 /// `TcpAcceptor` which can be used to accept sockets on a local port.
 ///
@@ -306,6 +1399,26 @@ impl sys_common::AsInner<TcpStreamImp> for TcpStream {
 /// ```
 pub struct TcpListener {
     inner: TcpListenerImp,
+    backlog: uint,
+    accept_defaults: Option<SocketOptions>,
+    accept_logger: Option<SharedAcceptLogger>,
+    byte_limit: Option<Arc<ByteLimit>>,
+    per_ip_limit: Option<Arc<PerIpLimit>>,
+    accept_nonblocking: bool,
+    accept_ttl: Option<int>,
+}
+
+// The logger installed by `TcpListener::set_accept_logger`, which is shared by
+// all clones of the acceptor.
+type SharedAcceptLogger = Arc<Mutex<Box<AcceptLogger + Send>>>;
+
+// An object-safe form of the closure given to `set_accept_logger`.
+trait AcceptLogger {
+    fn log(&mut self, peer: IpAddr, at: u64);
+}
+
+impl<F> AcceptLogger for F where F: FnMut(IpAddr, u64) {
+    fn log(&mut self, peer: IpAddr, at: u64) { (*self)(peer, at) }
 }
 
 impl TcpListener {
@@ -319,12 +1432,235 @@ impl TcpListener {
     ///
     /// The address type can be any implementor of `ToSocketAddr` trait. See its
     /// documentation for concrete examples.
+    ///
+    /// Both IPv4 and IPv6 addresses are supported. Whether a listener bound to
+    /// the unspecified IPv6 address `::` also accepts IPv4 connections is left
+    /// to the platform's default for `IPV6_V6ONLY`: Linux and Mac OS X accept
+    /// them unless configured otherwise, while Windows and OpenBSD do not.
     pub fn bind<A: ToSocketAddr>(addr: A) -> IoResult<TcpListener> {
+        TcpListener::bind_with_backlog(addr, DEFAULT_BACKLOG)
+    }
+
+    /// Creates a new `TcpListener` which will be bound to the specified
+    /// address, using `backlog` as the maximum length of the queue of pending
+    /// connections once `listen` is called.
+    ///
+    /// This is otherwise the same as the `bind` method, which uses a backlog of
+    /// 128.
+    #[experimental = "the backlog argument may change in type"]
+    pub fn bind_with_backlog<A: ToSocketAddr>(addr: A, backlog: uint)
+                                              -> IoResult<TcpListener> {
+        super::with_addresses(addr, |addr| {
+            TcpListenerImp::bind(addr).map(|inner| TcpListener::new(inner, backlog))
+        })
+    }
+
+    fn new(inner: TcpListenerImp, backlog: uint) -> TcpListener {
+        TcpListener {
+            inner: inner,
+            backlog: backlog,
+            accept_defaults: None,
+            accept_logger: None,
+            byte_limit: None,
+            per_ip_limit: None,
+            accept_nonblocking: false,
+            accept_ttl: None,
+        }
+    }
+
+    /// Returns the backlog which will be requested from the OS when this
+    /// listener starts listening.
+    ///
+    /// Note that this is the value which was passed at bind time. The OS may
+    /// silently clamp it to a system-wide maximum (for example,
+    /// `/proc/sys/net/core/somaxconn` on Linux), and the effective value is
+    /// not portably queryable.
+    #[experimental]
+    pub fn backlog(&self) -> uint {
+        self.backlog
+    }
+
+    /// Creates a new `TcpListener` bound to `port` on the wildcard address of
+    /// the given address family, so that it accepts connections made to any
+    /// of the host's addresses of that family.
+    ///
+    /// This is the same as `bind` with `0.0.0.0` or `::` as the address.
+    /// Whether a listener on `::` also accepts IPv4 connections depends on the
+    /// platform's configuration.
+    #[experimental]
+    pub fn bind_family(port: u16, family: AddressFamily) -> IoResult<TcpListener> {
+        let ip = match family {
+            AddressFamily::V4 => Ipv4Addr(0, 0, 0, 0),
+            AddressFamily::V6 => Ipv6Addr(0, 0, 0, 0, 0, 0, 0, 0),
+        };
+        TcpListener::bind(SocketAddr { ip: ip, port: port })
+    }
+
+    /// Creates a new `TcpListener` bound to the specified address with
+    /// `SO_REUSEPORT` set, so that several listeners can be bound to the same
+    /// address at once.
+    ///
+    /// Every listener sharing the address must be created this way. On Linux
+    /// the kernel spreads incoming connections across all of them, which
+    /// allows accepting to be scaled across several tasks; other Unix
+    /// platforms allow the address to be shared but do not balance
+    /// connections. Windows returns an `IoUnavailable` error.
+    #[experimental]
+    pub fn bind_reuse_port<A: ToSocketAddr>(addr: A) -> IoResult<TcpListener> {
+        super::with_addresses(addr, |addr| {
+            TcpListenerImp::bind_reuse_port(addr).map(|inner| {
+                TcpListener::new(inner, DEFAULT_BACKLOG)
+            })
+        })
+    }
+
+    /// Creates a new `TcpListener` bound to the specified address which only
+    /// accepts connections arriving on the network interface named `iface`,
+    /// such as `"eth0"`.
+    ///
+    /// This uses `SO_BINDTODEVICE`, and so is only supported on Linux and
+    /// Android, where it usually requires extra privileges; other platforms
+    /// return an `IoUnavailable` error. An interface name which is too long
+    /// results in an `InvalidInput` error.
+    #[experimental]
+    pub fn bind_device<A: ToSocketAddr>(addr: A, iface: &str) -> IoResult<TcpListener> {
         super::with_addresses(addr, |addr| {
-            TcpListenerImp::bind(addr).map(|inner| TcpListener { inner: inner })
+            TcpListenerImp::bind_device(addr, iface).map(|inner| {
+                TcpListener::new(inner, DEFAULT_BACKLOG)
+            })
+        })
+    }
+
+    /// Creates a new `TcpListener` bound to the first available port of `ip`
+    /// from `lo` to `hi`, inclusive.
+    ///
+    /// Each port is tried in turn, skipping over ports which are already in
+    /// use. Any other error encountered while binding is returned immediately.
+    /// If every port in the range is in use, an error of kind
+    /// `ResourceUnavailable` is returned, with the range in its detail.
+    ///
+    /// The port which was chosen can be queried via the `socket_name`
+    /// function.
+    #[experimental = "the range argument may change to a range type"]
+    pub fn bind_in_range(ip: IpAddr, lo: u16, hi: u16) -> IoResult<TcpListener> {
+        for port in range_inclusive(lo, hi) {
+            match TcpListener::bind(SocketAddr { ip: ip, port: port }) {
+                Ok(listener) => return Ok(listener),
+                // EADDRINUSE is reported as ConnectionRefused
+                Err(ref e) if e.kind == ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(IoError {
+            kind: ResourceUnavailable,
+            desc: "no available port in range",
+            detail: Some(format!("{}:{}-{}", ip, lo, hi)),
+            os_error: None,
         })
     }
 
+    /// Sets the options which will be applied to every stream accepted from
+    /// this listener's acceptor, including those yielded by `incoming`.
+    ///
+    /// If applying the options to an accepted stream fails, that error is
+    /// returned from `accept` in place of the stream.
+    ///
+    /// The defaults are carried over to clones of the acceptor.
+    #[experimental]
+    pub fn set_accept_defaults(&mut self, opts: SocketOptions) {
+        self.accept_defaults = Some(opts);
+    }
+
+    /// Sets whether streams accepted from this listener's acceptor start out
+    /// in nonblocking mode, as set by `TcpStream::set_nonblocking`.
+    ///
+    /// This applies to streams returned by `accept`, `try_accept` and
+    /// `incoming`, and is carried over to clones of the acceptor.
+    #[experimental]
+    pub fn set_accept_nonblocking(&mut self, nonblocking: bool) {
+        self.accept_nonblocking = nonblocking;
+    }
+
+    /// Sets the time-to-live which is applied with `TcpStream::set_ttl` to
+    /// every stream accepted from this listener's acceptor.
+    ///
+    /// As with `TcpStream::set_ttl`, the value must be between 1 and 255, or
+    /// an `InvalidInput` error is returned. If applying it to an accepted
+    /// stream fails, that error is returned from `accept` in place of the
+    /// stream.
+    #[experimental]
+    pub fn set_ttl(&mut self, ttl: int) -> IoResult<()> {
+        try!(check_ttl(ttl));
+        self.accept_ttl = Some(ttl);
+        Ok(())
+    }
+
+    /// Delays accepting connections until the client has sent some data
+    /// (`TCP_DEFER_ACCEPT`).
+    ///
+    /// With this set, `accept` does not return a connection until data has
+    /// arrived on it, which saves a wakeup for protocols where the client
+    /// speaks first. If no data arrives within roughly `seconds` seconds, the
+    /// kernel either drops the connection or hands it over without data,
+    /// depending on its version, so callers must still cope with connections
+    /// which have nothing to read. `None` restores the default behavior.
+    ///
+    /// This is only supported on Linux and Android; other platforms return an
+    /// `IoUnavailable` error.
+    #[experimental]
+    pub fn set_defer_accept(&mut self, seconds: Option<uint>) -> IoResult<()> {
+        self.inner.set_defer_accept(seconds)
+    }
+
+    /// Installs a function to be called with the address of the peer of
+    /// every connection accepted from this listener's acceptor, for auditing.
+    ///
+    /// The function also receives the time of the accept, in milliseconds,
+    /// from the clock used for I/O timeouts. Its starting point is
+    /// unspecified, and on unix it follows the system time, so it can jump
+    /// if the system clock is changed. The function is called from whichever
+    /// task accepted the connection, and is shared by all clones of the
+    /// acceptor. Installing a new logger replaces the previous one.
+    #[experimental]
+    pub fn set_accept_logger<F>(&mut self, logger: F)
+                                where F: FnMut(IpAddr, u64) + Send {
+        let logger = box logger as Box<AcceptLogger + Send>;
+        self.accept_logger = Some(Arc::new(Mutex::new(logger)));
+    }
+
+    /// Limits the total number of bytes which may be transferred over all
+    /// connections accepted from this listener's acceptor.
+    ///
+    /// Every byte read from or written to an accepted stream (or any of its
+    /// clones) through the `Reader` and `Writer` implementations counts
+    /// towards the limit. Once `bytes` bytes have been transferred, `accept`
+    /// no longer accepts connections and instead returns an error of kind
+    /// `ResourceUnavailable`. Streams which were already accepted are not
+    /// affected, and may exceed the limit.
+    #[experimental]
+    pub fn set_total_byte_limit(&mut self, bytes: u64) {
+        self.byte_limit = Some(Arc::new(ByteLimit {
+            limit: bytes,
+            used: Mutex::new(0),
+        }));
+    }
+
+    /// Limits the number of live connections accepted from any one peer
+    /// address to `max`.
+    ///
+    /// A connection counts towards the limit until it and all of its clones
+    /// have been dropped. When a connection arrives from an address which
+    /// already has `max` live connections, `accept` resets it and carries on
+    /// waiting for the next one, so the rejected connection is never
+    /// returned. The limit is shared by all clones of the acceptor.
+    #[experimental]
+    pub fn set_per_ip_limit(&mut self, max: uint) {
+        self.per_ip_limit = Some(Arc::new(PerIpLimit {
+            max: max,
+            active: Mutex::new(HashMap::new()),
+        }));
+    }
+
     /// Returns the local socket address of this listener.
     pub fn socket_name(&mut self) -> IoResult<SocketAddr> {
         self.inner.socket_name()
@@ -333,7 +1669,21 @@ impl TcpListener {
 
 impl Listener<TcpStream, TcpAcceptor> for TcpListener {
     fn listen(self) -> IoResult<TcpAcceptor> {
-        self.inner.listen(128).map(|a| TcpAcceptor { inner: a })
+        let TcpListener {
+            inner, backlog, accept_defaults, accept_logger, byte_limit,
+            per_ip_limit, accept_nonblocking, accept_ttl
+        } = self;
+        inner.listen(backlog as int).map(|a| {
+            TcpAcceptor {
+                inner: a,
+                accept_defaults: accept_defaults,
+                accept_logger: accept_logger,
+                byte_limit: byte_limit,
+                per_ip_limit: per_ip_limit,
+                accept_nonblocking: accept_nonblocking,
+                accept_ttl: accept_ttl,
+            }
+        })
     }
 }
 
@@ -343,11 +1693,88 @@ impl sys_common::AsInner<TcpListenerImp> for TcpListener {
     }
 }
 
+impl sys_common::FromInner<TcpListenerImp> for TcpListener {
+    fn from_inner(inner: TcpListenerImp) -> TcpListener {
+        TcpListener::new(inner, DEFAULT_BACKLOG)
+    }
+}
+
+// The connections whose handlers are running in a loop started by
+// `TcpAcceptor::serve_in_background`, keyed by the order they were accepted.
+struct ActiveConnections {
+    next: uint,
+    streams: HashMap<uint, TcpStream>,
+}
+
+type SharedActiveConnections = Arc<Mutex<ActiveConnections>>;
+
+// Held by a handler task for as long as it runs, even if it panics, to keep
+// its connection among the active ones.
+struct ActiveSlot {
+    active: SharedActiveConnections,
+    id: uint,
+}
+
+impl Drop for ActiveSlot {
+    fn drop(&mut self) {
+        self.active.lock().streams.remove(&self.id);
+    }
+}
+
+/// A handle to a loop serving connections in the background, created by
+/// `TcpAcceptor::serve_in_background`.
+#[experimental]
+pub struct ServerHandle {
+    acceptor: Mutex<TcpAcceptor>,
+    active: SharedActiveConnections,
+}
+
+impl ServerHandle {
+    /// Returns the number of connections whose handlers are still running.
+    #[experimental]
+    pub fn active_connections(&self) -> uint {
+        self.active.lock().streams.len()
+    }
+
+    /// Stops the loop accepting connections, and then waits up to `ms`
+    /// milliseconds for the handlers which are still running to finish.
+    ///
+    /// Any connections whose handlers are still running after that are reset:
+    /// their linger timeout is set to zero and they are shut down, so that the
+    /// peer sees the connection end and any further I/O by the handler
+    /// fails. The socket itself is closed once the handler drops its stream.
+    /// Returns the number of connections which were reset.
+    #[experimental]
+    pub fn stop_timeout(&self, ms: u64) -> IoResult<uint> {
+        try!(self.acceptor.lock().close_accept());
+
+        let deadline = timer::now() + ms;
+        while self.active_connections() > 0 {
+            let now = timer::now();
+            if now >= deadline { break }
+            sleep(Duration::milliseconds(cmp::min(deadline - now, 10) as i64));
+        }
+
+        let mut active = self.active.lock();
+        for (_, stream) in active.streams.iter_mut() {
+            let _ = stream.set_linger(Some(0));
+            let _ = stream.close();
+        }
+        Ok(active.streams.len())
+    }
+}
+
 /// The accepting half of a TCP socket server. This structure is created through
 /// a `TcpListener`'s `listen` method, and this object can be used to accept new
 /// `TcpStream` instances.
 pub struct TcpAcceptor {
     inner: TcpAcceptorImp,
+    accept_defaults: Option<SocketOptions>,
+    accept_logger: Option<SharedAcceptLogger>,
+    byte_limit: Option<Arc<ByteLimit>>,
+    per_ip_limit: Option<Arc<PerIpLimit>>,
+    accept_nonblocking: bool,
+    accept_ttl: Option<int>,
 }
 
 impl TcpAcceptor {
@@ -443,11 +1870,365 @@ impl TcpAcceptor {
     pub fn close_accept(&mut self) -> IoResult<()> {
         self.inner.close_accept()
     }
+
+    /// Accepts connections in a loop, handing each one to `handler` in a new
+    /// task.
+    ///
+    /// A panic in one of the handlers only takes down the task for that
+    /// connection, and the loop carries on accepting. So does an accept which
+    /// fails with an error that `is_accept_fatal` considers transient, after
+    /// backing off briefly unless only the one connection was affected. This
+    /// returns `Ok(())` once `close_accept` has been called on a clone of
+    /// this acceptor, and otherwise returns the first fatal error.
+    ///
+    /// A timeout set with `set_timeout` also ends the loop. Once it expires
+    /// every later accept would time out too, so the `TimedOut` error is
+    /// returned rather than retried.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # #![allow(experimental)]
+    /// use std::io::{TcpListener, Listener, Writer};
+    ///
+    /// let mut acceptor = TcpListener::bind("127.0.0.1:8482").listen().unwrap();
+    /// acceptor.serve(|&: mut stream| {
+    ///     stream.write(b"hello\n").unwrap();
+    /// }).unwrap();
+    /// ```
+    #[experimental]
+    pub fn serve<F>(&mut self, handler: F) -> IoResult<()>
+                    where F: Fn(TcpStream) + Send + Sync {
+        self.serve_tracked(|&mut: a: &mut TcpAcceptor| a.accept(), handler, None)
+    }
+
+    /// Runs `serve` with `handler` in a new task, and returns a handle which
+    /// can be used to stop it.
+    ///
+    /// If the loop stops because of a fatal accept error, the error is
+    /// discarded; `ServerHandle::stop_timeout` can still be used to wait for
+    /// the remaining handlers.
+    #[experimental]
+    pub fn serve_in_background<F>(self, handler: F) -> ServerHandle
+                                  where F: Fn(TcpStream) + Send + Sync {
+        let active = Arc::new(Mutex::new(ActiveConnections {
+            next: 0,
+            streams: HashMap::new(),
+        }));
+        let handle = ServerHandle {
+            acceptor: Mutex::new(self.clone()),
+            active: active.clone(),
+        };
+        let mut acceptor = self;
+        spawn(move|| {
+            let accept = |&mut: a: &mut TcpAcceptor| a.accept();
+            let _ = acceptor.serve_tracked(accept, handler, Some(active));
+        });
+        handle
+    }
+
+    // The loop behind `serve`, which takes each connection from `accept` and
+    // also records it in `active` while its handler runs, if given.
+    fn serve_tracked<A, F>(&mut self, mut accept: A, handler: F,
+                           active: Option<SharedActiveConnections>) -> IoResult<()>
+                           where A: FnMut(&mut TcpAcceptor) -> IoResult<TcpStream>,
+                                 F: Fn(TcpStream) + Send + Sync {
+        let handler = Arc::new(handler);
+        loop {
+            let stream = match accept(self) {
+                Ok(stream) => stream,
+                Err(ref e) if e.kind == EndOfFile => return Ok(()),
+                // The timeout is a deadline, so it won't clear up by itself
+                Err(ref e) if e.kind != TimedOut && !self.is_accept_fatal(e) => {
+                    // Don't spin while, say, out of file descriptors
+                    if e.kind != ConnectionAborted && e.kind != ConnectionReset {
+                        sleep(Duration::milliseconds(10));
+                    }
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            let slot = active.as_ref().map(|active| {
+                let mut a = active.lock();
+                let id = a.next;
+                a.next += 1;
+                a.streams.insert(id, stream.clone());
+                ActiveSlot { active: active.clone(), id: id }
+            });
+            let handler = handler.clone();
+            spawn(move|| {
+                let _slot = slot;
+                (*handler)(stream)
+            });
+        }
+    }
+
+    /// Returns whether `err`, an error returned by `accept` or `try_accept` on
+    /// this acceptor, means that it will not be able to accept any more
+    /// connections.
+    ///
+    /// Some errors only concern the one connection which was being accepted,
+    /// such as a connection which was aborted before it could be accepted,
+    /// and others may clear up by themselves, such as the process running
+    /// out of file descriptors, or may be retried, such as an accept timing
+    /// out once a new timeout has been set. These leave the
+    /// acceptor as it was, so a server can shed the failed connection and
+    /// carry on accepting. Other errors are fatal, as is the `EndOfFile`
+    /// error returned once `close_accept` has been called, or running out of
+    /// the listener's byte limit.
+    #[experimental]
+    pub fn is_accept_fatal(&self, err: &IoError) -> bool {
+        match err.kind {
+            EndOfFile => true,
+            ResourceUnavailable => {
+                self.byte_limit.as_ref().map_or(false, |l| l.exhausted())
+            }
+            ConnectionAborted | ConnectionReset | TimedOut => false,
+            _ => !err.os_error.map_or(false, TcpAcceptorImp::is_transient_error),
+        }
+    }
+
+    /// Accepts a connection if one is already pending, without blocking.
+    ///
+    /// Returns `Ok(None)` straight away if no connection is queued, which
+    /// makes it possible to drive the acceptor from a custom event loop. The
+    /// timeout set by `set_timeout` does not apply. Otherwise this behaves
+    /// like `accept`, and any other error is returned as normal.
+    #[experimental]
+    pub fn try_accept(&mut self) -> IoResult<Option<TcpStream>> {
+        try!(self.check_byte_limit());
+        loop {
+            match try!(self.inner.try_accept()) {
+                Some(inner) => match try!(self.admit(inner)) {
+                    Some(stream) => return Ok(Some(stream)),
+                    None => {}
+                },
+                None => return Ok(None),
+            }
+        }
+    }
+
+    fn check_byte_limit(&self) -> IoResult<()> {
+        match self.byte_limit {
+            Some(ref limit) if limit.exhausted() => {
+                Err(IoError {
+                    kind: ResourceUnavailable,
+                    desc: "the listener's byte limit has been reached",
+                    detail: None,
+                    os_error: None,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Applies this acceptor's settings to a newly accepted connection. If the
+    // per-ip limit rejects the connection, it is reset and `None` is returned.
+    fn admit(&mut self, inner: TcpStreamImp) -> IoResult<Option<TcpStream>> {
+        let mut stream = TcpStream::new(inner);
+        match self.per_ip_limit {
+            Some(ref limit) if !admit_peer(limit, &mut stream) => {
+                // Reset the connection rather than closing it gracefully, so
+                // the peer learns of the rejection straight away.
+                let _ = stream.set_linger(Some(0));
+                return Ok(None)
+            }
+            _ => {}
+        }
+        stream.byte_limit = self.byte_limit.clone();
+        stream.set_nonblocking(self.accept_nonblocking);
+        match self.accept_defaults {
+            Some(ref opts) => try!(stream.set_options(opts)),
+            None => {}
+        }
+        match self.accept_ttl {
+            Some(ttl) => try!(stream.set_ttl(ttl)),
+            None => {}
+        }
+        match self.accept_logger {
+            Some(ref logger) => {
+                let at = timer::now();
+                match stream.peer_name() {
+                    Ok(peer) => logger.lock().log(peer.ip, at),
+                    Err(..) => {}
+                }
+            }
+            None => {}
+        }
+        Ok(Some(stream))
+    }
+
+    /// Accepts a connection which begins with a PROXY protocol header, as
+    /// sent by load balancers to relay the address of the original client.
+    ///
+    /// Both the textual (version 1) and binary (version 2) forms of the header
+    /// are understood. The header is consumed, so the returned stream is
+    /// positioned at the first byte of the client's data, and the client
+    /// address it carried is returned along with the stream. If the header
+    /// does not carry an address (for example, for health checks made by the
+    /// load balancer itself), the address of the peer is returned instead.
+    ///
+    /// If the connection does not begin with a well-formed header, an error
+    /// of kind `InvalidInput` is returned and the connection is dropped. The
+    /// whole header must arrive within `timeout_ms` milliseconds of the
+    /// connection being accepted, otherwise an error of kind `TimedOut` is
+    /// returned and the connection is likewise dropped.
+    #[experimental = "the timeout argument may change in type and value"]
+    pub fn accept_proxy_protocol(&mut self, timeout_ms: u64)
+                                 -> IoResult<(TcpStream, SocketAddr)> {
+        let mut stream = try!(self.accept());
+        stream.set_read_timeout(Some(timeout_ms));
+        let addr = match try!(read_proxy_header(&mut stream)) {
+            Some(addr) => addr,
+            None => try!(stream.peer_name()),
+        };
+        stream.set_read_timeout(None);
+        Ok((stream, addr))
+    }
+}
+
+fn check_ttl(ttl: int) -> IoResult<()> {
+    if ttl < 1 || ttl > 255 {
+        Err(IoError {
+            kind: InvalidInput,
+            desc: "TTL must be between 1 and 255",
+            detail: Some(format!("{}", ttl)),
+            os_error: None,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+// The longest possible version 1 PROXY header, including the trailing CRLF
+const PROXY_V1_MAX_LEN: uint = 107;
+const PROXY_V2_SIGNATURE: &'static [u8] = b"\r\n\r\n\0\r\nQUIT\n";
+
+fn invalid_proxy_header() -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "invalid PROXY protocol header",
+        detail: None,
+        os_error: None,
+    }
+}
+
+// Reads a PROXY protocol header from the front of `s`, returning the source
+// address it carries, if any. Nothing beyond the header is read.
+fn read_proxy_header(s: &mut TcpStream) -> IoResult<Option<SocketAddr>> {
+    let mut start = [0u8, ..6];
+    try!(s.read_at_least(start.len(), &mut start));
+    if start[] == b"PROXY " {
+        read_proxy_v1(s)
+    } else if start[] == PROXY_V2_SIGNATURE[..start.len()] {
+        read_proxy_v2(s)
+    } else {
+        Err(invalid_proxy_header())
+    }
+}
+
+fn read_proxy_v1(s: &mut TcpStream) -> IoResult<Option<SocketAddr>> {
+    // The header is read a byte at a time so as not to consume any of the
+    // data which follows it.
+    let mut line = Vec::new();
+    loop {
+        let b = try!(s.read_byte());
+        line.push(b);
+        if b == b'\n' { break }
+        if line.len() + "PROXY ".len() >= PROXY_V1_MAX_LEN {
+            return Err(invalid_proxy_header())
+        }
+    }
+    if !line[].ends_with(b"\r\n") { return Err(invalid_proxy_header()) }
+    let line = match str::from_utf8(line[..line.len() - 2]) {
+        Some(line) => line,
+        None => return Err(invalid_proxy_header()),
+    };
+
+    let fields: Vec<&str> = line.split(' ').collect();
+    if fields.len() >= 1 && fields[0] == "UNKNOWN" { return Ok(None) }
+    if fields.len() != 5 || (fields[0] != "TCP4" && fields[0] != "TCP6") {
+        return Err(invalid_proxy_header())
+    }
+    let src: Option<IpAddr> = FromStr::from_str(fields[1]);
+    let dst: Option<IpAddr> = FromStr::from_str(fields[2]);
+    let src_port: Option<u16> = FromStr::from_str(fields[3]);
+    let dst_port: Option<u16> = FromStr::from_str(fields[4]);
+    match (src, dst, src_port, dst_port) {
+        (Some(src), Some(dst), Some(port), Some(..)) => {
+            // both addresses must belong to the family the header names
+            if !in_proxy_family(fields[0], &src) ||
+               !in_proxy_family(fields[0], &dst) {
+                return Err(invalid_proxy_header())
+            }
+            Ok(Some(SocketAddr { ip: src, port: port }))
+        }
+        _ => Err(invalid_proxy_header()),
+    }
+}
+
+fn in_proxy_family(family: &str, ip: &IpAddr) -> bool {
+    match *ip {
+        Ipv4Addr(..) => family == "TCP4",
+        Ipv6Addr(..) => family == "TCP6",
+    }
+}
+
+fn read_proxy_v2(s: &mut TcpStream) -> IoResult<Option<SocketAddr>> {
+    // The rest of the signature, then the version and command, the address
+    // family and the length of the address block.
+    let hdr = try!(s.read_exact(10));
+    if hdr[..6] != PROXY_V2_SIGNATURE[6..] || hdr[6] >> 4 != 2 {
+        return Err(invalid_proxy_header())
+    }
+    let cmd = hdr[6] & 0xf;
+    let family = hdr[7];
+    let len = (hdr[8] as uint << 8) | hdr[9] as uint;
+    let addrs = try!(s.read_exact(len));
+
+    let be16 = |&: b: &[u8]| (b[0] as u16 << 8) | b[1] as u16;
+    match (cmd, family) {
+        // LOCAL: the connection was made by the proxy itself
+        (0, _) => Ok(None),
+        // PROXY over TCP/IPv4
+        (1, 0x11) if addrs.len() >= 12 => {
+            let a = addrs[];
+            Ok(Some(SocketAddr {
+                ip: Ipv4Addr(a[0], a[1], a[2], a[3]),
+                port: be16(a[8..10]),
+            }))
+        }
+        // PROXY over TCP/IPv6
+        (1, 0x21) if addrs.len() >= 36 => {
+            let a = addrs[];
+            let w = |&: i: uint| be16(a[i * 2..i * 2 + 2]);
+            Ok(Some(SocketAddr {
+                ip: Ipv6Addr(w(0), w(1), w(2), w(3), w(4), w(5), w(6), w(7)),
+                port: be16(a[32..34]),
+            }))
+        }
+        // PROXY over some other transport, which has no address we can use
+        (1, _) => Ok(None),
+        _ => Err(invalid_proxy_header()),
+    }
 }
 
 impl Acceptor<TcpStream> for TcpAcceptor {
     fn accept(&mut self) -> IoResult<TcpStream> {
-        self.inner.accept().map(TcpStream::new)
+        try!(self.check_byte_limit());
+        loop {
+            let inner = match self.inner.accept() {
+                Ok(inner) => inner,
+                Err(e) => {
+                    let addr = self.inner.socket_name();
+                    return Err(add_detail(e, "accept on", addr))
+                }
+            };
+            match try!(self.admit(inner)) {
+                Some(stream) => return Ok(stream),
+                None => {}
+            }
+        }
     }
 }
 
@@ -466,7 +2247,15 @@ impl Clone for TcpAcceptor {
     /// This function is useful for creating a handle to invoke `close_accept`
     /// on to wake up any other task blocked in `accept`.
     fn clone(&self) -> TcpAcceptor {
-        TcpAcceptor { inner: self.inner.clone() }
+        TcpAcceptor {
+            inner: self.inner.clone(),
+            accept_defaults: self.accept_defaults,
+            accept_logger: self.accept_logger.clone(),
+            byte_limit: self.byte_limit.clone(),
+            per_ip_limit: self.per_ip_limit.clone(),
+            accept_nonblocking: self.accept_nonblocking,
+            accept_ttl: self.accept_ttl,
+        }
     }
 }
 
@@ -479,11 +2268,13 @@ impl sys_common::AsInner<TcpAcceptorImp> for TcpAcceptor {
 #[cfg(test)]
 #[allow(experimental)]
 mod test {
+    use default::Default;
     use io::net::tcp::*;
     use io::net::ip::*;
     use io::*;
     use io::test::*;
     use prelude::*;
+    use time::Duration;
 
     // FIXME #11530 this fails on android because tests are run as root
     #[cfg_attr(any(windows, target_os = "android"), ignore)]
@@ -504,40 +2295,142 @@ mod test {
     }
 
     #[test]
-    fn listen_ip4_localhost() {
-        let socket_addr = next_test_ip4();
-        let listener = TcpListener::bind(socket_addr);
-        let mut acceptor = listener.listen();
+    fn connect_error_os_error() {
+        use libc;
+        #[cfg(unix)] const ECONNREFUSED: i32 = libc::ECONNREFUSED;
+        #[cfg(windows)] const ECONNREFUSED: i32 = libc::WSAECONNREFUSED;
 
-        spawn(move|| {
-            let mut stream = TcpStream::connect(("localhost", socket_addr.port));
-            stream.write(&[144]).unwrap();
-        });
+        let e = TcpStream::connect("0.0.0.0:1").err().unwrap();
+        assert_eq!(e.os_error, Some(ECONNREFUSED));
 
-        let mut stream = acceptor.accept();
-        let mut buf = [0];
-        stream.read(&mut buf).unwrap();
-        assert!(buf[0] == 144);
+        // Errors which don't come from the OS have no code
+        let e = TcpStream::connect("").err().unwrap();
+        assert_eq!(e.os_error, None);
     }
 
     #[test]
-    fn connect_localhost() {
+    fn error_detail() {
         let addr = next_test_ip4();
         let mut acceptor = TcpListener::bind(addr).listen();
-
+        let (tx, rx) = channel();
         spawn(move|| {
-            let mut stream = TcpStream::connect(("localhost", addr.port));
-            stream.write(&[64]).unwrap();
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.close_write().unwrap();
+            tx.send(s.write(&[0]).err().unwrap());
         });
 
-        let mut stream = acceptor.accept();
-        let mut buf = [0];
-        stream.read(&mut buf).unwrap();
-        assert!(buf[0] == 64);
+        let _s = acceptor.accept().unwrap();
+        let detail = rx.recv().detail.unwrap();
+        let expected = format!("write to {}", addr);
+        assert!(detail[].contains(expected[]), "{}", detail);
     }
 
     #[test]
-    fn connect_ip4_loopback() {
+    fn accept_after_transient_error() {
+        use libc;
+        #[cfg(unix)] const EMFILE: i32 = libc::EMFILE;
+        #[cfg(unix)] const EBADF: i32 = libc::EBADF;
+        #[cfg(windows)] const EMFILE: i32 = libc::WSAEMFILE;
+        #[cfg(windows)]
+        const EBADF: i32 = libc::consts::os::c95::WSAENOTSOCK;
+
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+
+        acceptor.set_timeout(Some(0));
+        let err = acceptor.accept().err().unwrap();
+        assert_eq!(err.kind, TimedOut);
+        assert!(!acceptor.is_accept_fatal(&err));
+
+        // the acceptor is still usable afterwards
+        acceptor.set_timeout(None);
+        let _s = TcpStream::connect(addr).unwrap();
+        assert!(acceptor.accept().is_ok());
+
+        let err = |code| IoError {
+            kind: OtherIoError,
+            desc: "unknown error",
+            detail: None,
+            os_error: Some(code),
+        };
+        assert!(!acceptor.is_accept_fatal(&err(EMFILE)));
+        assert!(acceptor.is_accept_fatal(&err(EBADF)));
+
+        acceptor.close_accept().unwrap();
+        let err = acceptor.accept().err().unwrap();
+        assert!(acceptor.is_accept_fatal(&err));
+    }
+
+    #[test]
+    fn probe() {
+        let addr = next_test_ip4();
+        assert!(!TcpStream::probe(addr, Duration::milliseconds(500)));
+
+        let _acceptor = TcpListener::bind(addr).listen().unwrap();
+        assert!(TcpStream::probe(addr, Duration::milliseconds(500)));
+    }
+
+    #[test]
+    fn connect_timeout_zero() {
+        let addr = next_test_ip4();
+        let _acceptor = TcpListener::bind(addr).listen().unwrap();
+
+        // Whether the connection completes immediately depends on the
+        // platform, but the attempt must not wait.
+        match TcpStream::connect_timeout(addr, Duration::milliseconds(0)) {
+            Ok(..) => {}
+            Err(e) => assert_eq!(e.kind, TimedOut),
+        }
+    }
+
+    #[test]
+    fn reachability() {
+        let addr = next_test_ip4();
+        let timeout = Duration::milliseconds(500);
+        assert_eq!(TcpStream::reachability(addr, timeout), Reachability::Closed);
+
+        let _acceptor = TcpListener::bind(addr).listen().unwrap();
+        assert_eq!(TcpStream::reachability(addr, timeout), Reachability::Open);
+
+        let timeout = Duration::milliseconds(-1);
+        assert_eq!(TcpStream::reachability(addr, timeout), Reachability::Timeout);
+    }
+
+    #[test]
+    fn listen_ip4_localhost() {
+        let socket_addr = next_test_ip4();
+        let listener = TcpListener::bind(socket_addr);
+        let mut acceptor = listener.listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(("localhost", socket_addr.port));
+            stream.write(&[144]).unwrap();
+        });
+
+        let mut stream = acceptor.accept();
+        let mut buf = [0];
+        stream.read(&mut buf).unwrap();
+        assert!(buf[0] == 144);
+    }
+
+    #[test]
+    fn connect_localhost() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(("localhost", addr.port));
+            stream.write(&[64]).unwrap();
+        });
+
+        let mut stream = acceptor.accept();
+        let mut buf = [0];
+        stream.read(&mut buf).unwrap();
+        assert!(buf[0] == 64);
+    }
+
+    #[test]
+    fn connect_ip4_loopback() {
         let addr = next_test_ip4();
         let mut acceptor = TcpListener::bind(addr).listen();
 
@@ -584,6 +2477,76 @@ mod test {
         assert!(buf[0] == 99);
     }
 
+    #[test]
+    fn flush_after_write() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr);
+            stream.write(&[99]).unwrap();
+            stream.flush().unwrap();
+        });
+
+        let mut stream = acceptor.accept();
+        let mut buf = [0];
+        stream.read(&mut buf).unwrap();
+        assert!(buf[0] == 99);
+    }
+
+    #[test]
+    fn smoke_test_nodelay() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_nodelay(true).unwrap();
+            stream.write(&[99]).unwrap();
+        });
+
+        let mut stream = acceptor.accept();
+        let mut buf = [0];
+        stream.read(&mut buf).unwrap();
+        assert!(buf[0] == 99);
+    }
+
+    #[test]
+    fn smoke_test_keepalive() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(&[99]).unwrap();
+            assert_eq!(stream.read_byte(), Ok(100));
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        stream.set_keepalive(Some(10)).unwrap();
+        assert_eq!(stream.read_byte(), Ok(99));
+        stream.write(&[100]).unwrap();
+    }
+
+    #[test]
+    fn set_ttl() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        assert_eq!(listener.set_ttl(256).err().unwrap().kind, InvalidInput);
+        listener.set_ttl(64).unwrap();
+        let mut acceptor = listener.listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            assert_eq!(stream.set_ttl(0).err().unwrap().kind, InvalidInput);
+            stream.set_ttl(1).unwrap();
+            stream.write(&[99]).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.read_byte(), Ok(99));
+    }
+
     #[test]
     fn smoke_test_ip6() {
         let addr = next_test_ip6();
@@ -632,6 +2595,123 @@ mod test {
         assert!(nread.is_err());
     }
 
+    #[test]
+    fn peek() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1, 2, 3]).unwrap();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        let mut peeked = [0, 0];
+        assert_eq!(s.peek(&mut peeked), Ok(2));
+        assert_eq!(s.read_exact(3).unwrap(), vec![1, 2, 3]);
+        assert_eq!(peeked[], [1, 2][]);
+        assert_eq!(s.peek(&mut peeked).err().unwrap().kind, EndOfFile);
+    }
+
+    #[test]
+    fn detect_tls() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        let client_hello = [0x16u8, 0x03, 0x01, 0x00, 0x05, 0x01, 0, 0, 1, 0];
+        let request = b"GET / HTTP/1.0\r\n\r\n";
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&client_hello).unwrap();
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(request).unwrap();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        assert!(s.detect_tls().unwrap());
+        assert_eq!(s.read_to_end().unwrap(), client_hello.to_vec());
+
+        let mut s = acceptor.accept().unwrap();
+        assert!(!s.detect_tls().unwrap());
+        assert_eq!(s.read_to_end().unwrap(), request.to_vec());
+    }
+
+    #[test]
+    fn read_at_least_split_writes() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1, 2]).unwrap();
+            rx.recv();
+            s.write(&[3, 4]).unwrap();
+            drop(s);
+
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[5, 6]).unwrap();
+        });
+
+        // The buffer is only filled once both writes have arrived
+        let mut s = acceptor.accept().unwrap();
+        let mut buf = [0, ..4];
+        tx.send(());
+        assert_eq!(s.read_at_least(4, &mut buf), Ok(4));
+        assert_eq!(buf[], [1, 2, 3, 4][]);
+
+        // and if the peer goes away first, the read fails
+        let mut s = acceptor.accept().unwrap();
+        assert_eq!(s.read_at_least(4, &mut buf).err().unwrap().kind, EndOfFile);
+    }
+
+    #[test]
+    fn total_bytes() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1, 2]).unwrap();
+            s.write(&[3, 4, 5]).unwrap();
+            tx.send((s.total_bytes_read(), s.total_bytes_written()));
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        let mut buf = [0, ..16];
+        let mut n = 0;
+        while n < 5 {
+            n += s.read(buf[mut n..]).unwrap();
+        }
+        assert!(s.read(&mut buf).is_err());
+        assert_eq!(s.total_bytes_read(), 5);
+        assert_eq!(s.total_bytes_written(), 0);
+        assert_eq!(rx.recv(), (0, 5));
+    }
+
+    #[test]
+    fn eof_ip4() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr);
+            stream.write(&[1]).unwrap();
+            // Close
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert!(!stream.eof());
+        let mut buf = [0];
+        assert_eq!(stream.read(&mut buf), Ok(1));
+        assert!(!stream.eof());
+        assert!(stream.read(&mut buf).is_err());
+        assert!(stream.eof());
+        assert!(stream.read(&mut buf).is_err());
+        assert!(stream.eof());
+    }
+
     #[test]
     fn read_eof_twice_ip4() {
         let addr = next_test_ip4();
@@ -731,266 +2811,1695 @@ mod test {
     }
 
     #[test]
-    fn multiple_connect_serial_ip4() {
+    fn on_disconnect_fires() {
         let addr = next_test_ip4();
-        let max = 10u;
         let mut acceptor = TcpListener::bind(addr).listen();
 
+        let (tx, rx) = channel();
         spawn(move|| {
-            for _ in range(0, max) {
-                let mut stream = TcpStream::connect(addr);
-                stream.write(&[99]).unwrap();
-            }
+            drop(TcpStream::connect(addr));
+            tx.send(());
         });
 
-        for ref mut stream in acceptor.incoming().take(max) {
-            let mut buf = [0];
-            stream.read(&mut buf).unwrap();
-            assert_eq!(buf[0], 99);
+        let mut stream = acceptor.accept().unwrap();
+        let (cbtx, cbrx) = channel();
+        stream.on_disconnect(move |: e: IoError| cbtx.send(e.kind));
+        rx.recv();
+
+        // The peer is gone, so writes eventually start failing
+        for i in range(0i, 1001) {
+            if stream.write(&[0]).is_err() { break }
+            if i == 1000 { panic!("peer never went away?!") }
         }
+        let kind = cbrx.recv();
+        assert!(kind == ConnectionReset ||
+                kind == BrokenPipe ||
+                kind == ConnectionAborted ||
+                kind == TimedOut,
+                "unknown error: {}", kind);
     }
 
     #[test]
-    fn multiple_connect_serial_ip6() {
-        let addr = next_test_ip6();
-        let max = 10u;
+    fn on_disconnect_fires_on_read() {
+        let addr = next_test_ip4();
         let mut acceptor = TcpListener::bind(addr).listen();
 
         spawn(move|| {
-            for _ in range(0, max) {
-                let mut stream = TcpStream::connect(addr);
-                stream.write(&[99]).unwrap();
-            }
+            // reset the connection rather than closing it gracefully
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_linger(Some(0)).unwrap();
         });
 
-        for ref mut stream in acceptor.incoming().take(max) {
-            let mut buf = [0];
-            stream.read(&mut buf).unwrap();
-            assert_eq!(buf[0], 99);
+        let mut stream = acceptor.accept().unwrap();
+        let (cbtx, cbrx) = channel();
+        stream.on_disconnect(move |: e: IoError| cbtx.send(e.kind));
+
+        // nothing is ever written, the read alone notices the reset
+        assert!(stream.read_byte().is_err());
+        assert_eq!(cbrx.recv(), ConnectionReset);
+    }
+
+    #[test]
+    fn on_disconnect_fires_on_probe_alive() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_linger(Some(0)).unwrap();
+            drop(s);
+            tx.send(());
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        let (cbtx, cbrx) = channel();
+        stream.on_disconnect(move |: e: IoError| cbtx.send(e.kind));
+        rx.recv();
+
+        for i in range(0i, 1001) {
+            if !stream.probe_alive() { break }
+            if i == 1000 { panic!("reset never arrived?!") }
+            ::io::timer::sleep(Duration::milliseconds(10));
         }
+        assert_eq!(cbrx.recv(), ConnectionReset);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn read_timestamped() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr);
+            // don't send anything until timestamping is on
+            rx.recv();
+            stream.write(&[1, 2]).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        stream.set_timestamping(true).unwrap();
+        tx.send(());
+        let mut buf = [0, ..2];
+        let (n, stamp) = stream.read_timestamped(&mut buf).unwrap();
+        assert!(n > 0);
+        assert_eq!(buf[0], 1);
+        assert!(stamp.unwrap() > 0);
+    }
+
+    #[test]
+    fn read_pooled() {
+        use io::util::BufferPool;
+
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr);
+            stream.write(&[1]).unwrap();
+            rx.recv();
+            stream.write(&[2]).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        let mut pool = BufferPool::new(128);
+        let first = {
+            let buf = stream.read_pooled(&mut pool).unwrap();
+            assert_eq!(buf[], [1][]);
+            buf.as_ptr()
+        };
+        tx.send(());
+        let buf = stream.read_pooled(&mut pool).unwrap();
+        assert_eq!(buf[], [2][]);
+        assert_eq!(buf.as_ptr(), first);
+    }
+
+    #[test]
+    fn writev() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.writev(&[b"head", b"", b"body"]).unwrap();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        assert_eq!(s.read_to_end().unwrap(), b"headbody".to_vec());
+    }
+
+    #[test]
+    fn writev_timeout() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let _s = TcpStream::connect(addr).unwrap();
+            // never read, so the second buffer can't all be sent
+            rx.recv();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        s.set_write_timeout(Some(200));
+        let small = [0u8, ..1024];
+        let big = Vec::from_elem(64 * 1024 * 1024, 0u8);
+        match s.writev(&[small[], big[]]) {
+            Err(IoError { kind: ShortWrite(n), .. }) => {
+                // the partial count includes the first buffer
+                assert!(n > small.len() && n < small.len() + big.len(),
+                        "wrote {}", n);
+            }
+            ret => panic!("unexpected result: {}", ret),
+        }
+        tx.send(());
+    }
+
+    #[test]
+    fn readv() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1, 2, 3, 4, 5]).unwrap();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        let mut a = [0, 0];
+        let mut b = [0, 0, 0];
+        // with nowhere to put data nothing is read, and the stream isn't at EOF
+        assert_eq!(s.readv(&mut [a[mut ..0], b[mut ..0]]), Ok(0));
+        let n = s.readv(&mut [a[mut], b[mut]]).unwrap();
+        assert!(n > 0);
+
+        // The first n bytes read are spread across the buffers in order
+        let mut got = a.to_vec();
+        got.push_all(&b);
+        got.truncate(n);
+        got.push_all(s.read_to_end().unwrap()[]);
+        assert_eq!(got, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn buffer_sizes() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let _s = acceptor.accept().unwrap();
+        });
+
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.set_recv_buffer_size(64 * 1024).unwrap();
+        assert!(s.recv_buffer_size().unwrap() >= 64 * 1024);
+        s.set_send_buffer_size(64 * 1024).unwrap();
+        assert!(s.send_buffer_size().unwrap() >= 64 * 1024);
+    }
+
+    #[test]
+    fn set_linger() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_linger(Some(1)).unwrap();
+            s.write(&[1, 2, 3]).unwrap();
+            drop(s);
+
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_linger(Some(1)).unwrap();
+            s.set_linger(None).unwrap();
+            s.write(&[4]).unwrap();
+        });
+
+        // A lingering close still delivers everything that was written.
+        let mut s = acceptor.accept().unwrap();
+        assert_eq!(s.read_to_end().unwrap(), vec![1, 2, 3]);
+        let mut s = acceptor.accept().unwrap();
+        assert_eq!(s.read_to_end().unwrap(), vec![4]);
+    }
+
+    #[test]
+    fn probe_alive() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        let (done, rxdone) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            // make closing the stream send a reset
+            stream.set_linger(Some(0)).unwrap();
+            drop(stream);
+            done.send(());
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert!(stream.probe_alive());
+        tx.send(());
+        rxdone.recv();
+
+        for i in range(0i, 1001) {
+            if !stream.probe_alive() { break }
+            ::task::deschedule();
+            if i == 1000 { panic!("never noticed the reset") }
+        }
+    }
+
+    #[test]
+    fn multiple_connect_serial_ip4() {
+        let addr = next_test_ip4();
+        let max = 10u;
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            for _ in range(0, max) {
+                let mut stream = TcpStream::connect(addr);
+                stream.write(&[99]).unwrap();
+            }
+        });
+
+        for ref mut stream in acceptor.incoming().take(max) {
+            let mut buf = [0];
+            stream.read(&mut buf).unwrap();
+            assert_eq!(buf[0], 99);
+        }
+    }
+
+    #[test]
+    fn multiple_connect_serial_ip6() {
+        let addr = next_test_ip6();
+        let max = 10u;
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            for _ in range(0, max) {
+                let mut stream = TcpStream::connect(addr);
+                stream.write(&[99]).unwrap();
+            }
+        });
+
+        for ref mut stream in acceptor.incoming().take(max) {
+            let mut buf = [0];
+            stream.read(&mut buf).unwrap();
+            assert_eq!(buf[0], 99);
+        }
+    }
+
+    #[test]
+    fn serve() {
+        let addr = next_test_ip4();
+        let acceptor = TcpListener::bind(addr).listen().unwrap();
+        let mut closer = acceptor.clone();
+        let (tx, rx) = channel();
+
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            let ret = acceptor.serve(|&: mut stream| {
+                let b = stream.read_byte().unwrap();
+                if b == 0 { panic!() }
+                stream.write_u8(b).unwrap();
+            });
+            tx.send(ret);
+        });
+
+        // a panicking handler leaves the accept loop running
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_u8(0).unwrap();
+        assert_eq!(s.read_byte().err().unwrap().kind, EndOfFile);
+
+        let (done_tx, done_rx) = channel();
+        for i in range(1u8, 11) {
+            let done_tx = done_tx.clone();
+            spawn(move|| {
+                let mut s = TcpStream::connect(addr).unwrap();
+                s.write_u8(i).unwrap();
+                assert_eq!(s.read_byte(), Ok(i));
+                done_tx.send(());
+            });
+        }
+        for _ in range(0u, 10) {
+            done_rx.recv();
+        }
+
+        closer.close_accept().unwrap();
+        assert!(rx.recv().is_ok());
+    }
+
+    #[test]
+    fn stop_timeout() {
+        let addr = next_test_ip4();
+        let acceptor = TcpListener::bind(addr).listen().unwrap();
+        let handle = acceptor.serve_in_background(|&: mut stream: TcpStream| {
+            stream.read_byte().unwrap();
+            // much slower than the stop timeout
+            ::io::timer::sleep(Duration::milliseconds(3000));
+            let _ = stream.write_u8(1);
+        });
+
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_u8(0).unwrap();
+        for _ in range(0u, 500) {
+            if handle.active_connections() == 1 { break }
+            ::io::timer::sleep(Duration::milliseconds(10));
+        }
+        assert_eq!(handle.active_connections(), 1);
+
+        let start = ::sys::timer::now();
+        assert_eq!(handle.stop_timeout(100), Ok(1));
+        assert!(::sys::timer::now() - start < 2000);
+
+        // the lingering connection was cut off rather than answered
+        s.set_read_timeout(Some(2000));
+        assert!(s.read_byte().is_err());
+    }
+
+    #[test]
+    fn stop_timeout_idle() {
+        let addr = next_test_ip4();
+        let acceptor = TcpListener::bind(addr).listen().unwrap();
+        let handle = acceptor.serve_in_background(|&: mut stream: TcpStream| {
+            let _ = stream.write_u8(1);
+        });
+
+        let mut s = TcpStream::connect(addr).unwrap();
+        assert_eq!(s.read_byte(), Ok(1));
+        assert_eq!(handle.stop_timeout(1000), Ok(0));
+    }
+
+    #[test]
+    fn serve_transient_error() {
+        use libc;
+        #[cfg(unix)] const EMFILE: i32 = libc::EMFILE;
+        #[cfg(windows)] const EMFILE: i32 = libc::WSAEMFILE;
+
+        let addr = next_test_ip4();
+        let acceptor = TcpListener::bind(addr).listen().unwrap();
+        let mut closer = acceptor.clone();
+        let (tx, rx) = channel();
+
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            // the first accept fails as if the process were out of fds
+            let mut failed = false;
+            let accept = |&mut: a: &mut TcpAcceptor| {
+                if failed { return a.accept() }
+                failed = true;
+                Err(IoError {
+                    kind: OtherIoError,
+                    desc: "too many open files",
+                    detail: None,
+                    os_error: Some(EMFILE),
+                })
+            };
+            tx.send(acceptor.serve_tracked(accept, |&: mut stream: TcpStream| {
+                let b = stream.read_byte().unwrap();
+                stream.write_u8(b).unwrap();
+            }, None));
+        });
+
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_u8(7).unwrap();
+        assert_eq!(s.read_byte(), Ok(7));
+
+        closer.close_accept().unwrap();
+        assert_eq!(rx.recv(), Ok(()));
+    }
+
+    #[test]
+    fn serve_timeout() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        acceptor.set_timeout(Some(10));
+        match acceptor.serve(|&: _stream| {}) {
+            Err(ref e) if e.kind == TimedOut => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(()) => panic!("serve returned without being closed"),
+        }
+    }
+
+    #[test]
+    fn multiple_connect_interleaved_greedy_schedule_ip4() {
+        let addr = next_test_ip4();
+        static MAX: int = 10;
+        let acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            for (i, stream) in acceptor.incoming().enumerate().take(MAX as uint) {
+                // Start another task to handle the connection
+                spawn(move|| {
+                    let mut stream = stream;
+                    let mut buf = [0];
+                    stream.read(&mut buf).unwrap();
+                    assert!(buf[0] == i as u8);
+                    debug!("read");
+                });
+            }
+        });
+
+        connect(0, addr);
+
+        fn connect(i: int, addr: SocketAddr) {
+            if i == MAX { return }
+
+            spawn(move|| {
+                debug!("connecting");
+                let mut stream = TcpStream::connect(addr);
+                // Connect again before writing
+                connect(i + 1, addr);
+                debug!("writing");
+                stream.write(&[i as u8]).unwrap();
+            });
+        }
+    }
+
+    #[test]
+    fn multiple_connect_interleaved_greedy_schedule_ip6() {
+        let addr = next_test_ip6();
+        static MAX: int = 10;
+        let acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            for (i, stream) in acceptor.incoming().enumerate().take(MAX as uint) {
+                // Start another task to handle the connection
+                spawn(move|| {
+                    let mut stream = stream;
+                    let mut buf = [0];
+                    stream.read(&mut buf).unwrap();
+                    assert!(buf[0] == i as u8);
+                    debug!("read");
+                });
+            }
+        });
+
+        connect(0, addr);
+
+        fn connect(i: int, addr: SocketAddr) {
+            if i == MAX { return }
+
+            spawn(move|| {
+                debug!("connecting");
+                let mut stream = TcpStream::connect(addr);
+                // Connect again before writing
+                connect(i + 1, addr);
+                debug!("writing");
+                stream.write(&[i as u8]).unwrap();
+            });
+        }
+    }
+
+    #[test]
+    fn multiple_connect_interleaved_lazy_schedule_ip4() {
+        static MAX: int = 10;
+        let addr = next_test_ip4();
+        let acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            for stream in acceptor.incoming().take(MAX as uint) {
+                // Start another task to handle the connection
+                spawn(move|| {
+                    let mut stream = stream;
+                    let mut buf = [0];
+                    stream.read(&mut buf).unwrap();
+                    assert!(buf[0] == 99);
+                    debug!("read");
+                });
+            }
+        });
+
+        connect(0, addr);
+
+        fn connect(i: int, addr: SocketAddr) {
+            if i == MAX { return }
+
+            spawn(move|| {
+                debug!("connecting");
+                let mut stream = TcpStream::connect(addr);
+                // Connect again before writing
+                connect(i + 1, addr);
+                debug!("writing");
+                stream.write(&[99]).unwrap();
+            });
+        }
+    }
+
+    #[test]
+    fn multiple_connect_interleaved_lazy_schedule_ip6() {
+        static MAX: int = 10;
+        let addr = next_test_ip6();
+        let acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            for stream in acceptor.incoming().take(MAX as uint) {
+                // Start another task to handle the connection
+                spawn(move|| {
+                    let mut stream = stream;
+                    let mut buf = [0];
+                    stream.read(&mut buf).unwrap();
+                    assert!(buf[0] == 99);
+                    debug!("read");
+                });
+            }
+        });
+
+        connect(0, addr);
+
+        fn connect(i: int, addr: SocketAddr) {
+            if i == MAX { return }
+
+            spawn(move|| {
+                debug!("connecting");
+                let mut stream = TcpStream::connect(addr);
+                // Connect again before writing
+                connect(i + 1, addr);
+                debug!("writing");
+                stream.write(&[99]).unwrap();
+            });
+        }
+    }
+
+    pub fn socket_name(addr: SocketAddr) {
+        let mut listener = TcpListener::bind(addr).unwrap();
+
+        // Make sure socket_name gives
+        // us the socket we binded to.
+        let so_name = listener.socket_name();
+        assert!(so_name.is_ok());
+        assert_eq!(addr, so_name.unwrap());
+    }
+
+    pub fn peer_name(addr: SocketAddr) {
+        let acceptor = TcpListener::bind(addr).listen();
+        spawn(move|| {
+            let mut acceptor = acceptor;
+            acceptor.accept().unwrap();
+        });
+
+        let stream = TcpStream::connect(addr);
+
+        assert!(stream.is_ok());
+        let mut stream = stream.unwrap();
+
+        // Make sure peer_name gives us the
+        // address/port of the peer we've
+        // connected to.
+        let peer_name = stream.peer_name();
+        assert!(peer_name.is_ok());
+        assert_eq!(addr, peer_name.unwrap());
+    }
+
+    #[test]
+    fn socket_and_peer_name_ip4() {
+        peer_name(next_test_ip4());
+        socket_name(next_test_ip4());
+    }
+
+    #[test]
+    fn accepted_peer_name_ip4() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            tx.send(stream.socket_name().unwrap());
+        });
+
+        // The accepting side sees the connecting side's ephemeral address.
+        let mut stream = acceptor.accept().unwrap();
+        let local = rx.recv();
+        assert_eq!(stream.peer_name().unwrap(), local);
+        assert!(local.port != 0 && local.port != addr.port);
+        assert_eq!(stream.socket_name().unwrap(), addr);
+    }
+
+    #[test]
+    fn bind_port_zero() {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.socket_name().unwrap();
+        assert_eq!(addr.ip, Ipv4Addr(127, 0, 0, 1));
+        assert!(addr.port != 0);
+        let mut acceptor = listener.listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(&[99]).unwrap();
+        });
+        assert_eq!(acceptor.accept().unwrap().read_byte(), Ok(99));
+    }
+
+    #[test]
+    fn socket_and_peer_name_ip6() {
+        // FIXME: peer name is not consistent
+        //peer_name(next_test_ip6());
+        socket_name(next_test_ip6());
+    }
+
+    #[test]
+    fn partial_read() {
+        let addr = next_test_ip4();
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut srv = TcpListener::bind(addr).listen().unwrap();
+            tx.send(());
+            let mut cl = srv.accept().unwrap();
+            cl.write(&[10]).unwrap();
+            let mut b = [0];
+            cl.read(&mut b).unwrap();
+            tx.send(());
+        });
+
+        rx.recv();
+        let mut c = TcpStream::connect(addr).unwrap();
+        let mut b = [0, ..10];
+        assert_eq!(c.read(&mut b), Ok(1));
+        c.write(&[1]).unwrap();
+        rx.recv();
+    }
+
+    #[test]
+    fn double_bind() {
+        let addr = next_test_ip4();
+        let listener = TcpListener::bind(addr).unwrap().listen();
+        assert!(listener.is_ok());
+        match TcpListener::bind(addr).listen() {
+            Ok(..) => panic!(),
+            Err(e) => {
+                assert!(e.kind == ConnectionRefused || e.kind == OtherIoError,
+                        "unknown error: {} {}", e, e.kind);
+            }
+        }
+    }
+
+    #[test]
+    fn bind_in_range_skips_used_port() {
+        use num::Int;
+
+        // grab two adjacent test ports
+        let mut lo = next_test_port();
+        let mut hi = next_test_port();
+        while lo.checked_add(1) != Some(hi) {
+            lo = hi;
+            hi = next_test_port();
+        }
+        let ip = Ipv4Addr(127, 0, 0, 1);
+        let _taken = TcpListener::bind(SocketAddr { ip: ip, port: lo })
+                                 .listen().unwrap();
+
+        let mut listener = TcpListener::bind_in_range(ip, lo, hi).unwrap();
+        assert_eq!(listener.socket_name().unwrap().port, hi);
+    }
+
+    #[test]
+    fn bind_in_range_exhausted() {
+        let addr = next_test_ip4();
+        let _taken = TcpListener::bind(addr).listen().unwrap();
+        match TcpListener::bind_in_range(addr.ip, addr.port, addr.port) {
+            Ok(..) => panic!(),
+            Err(e) => {
+                assert_eq!(e.kind, ResourceUnavailable);
+                let range = format!("{}:{}-{}", addr.ip, addr.port, addr.port);
+                assert_eq!(e.detail, Some(range));
+            }
+        }
+    }
+
+    #[test]
+    fn bind_with_backlog() {
+        let addr = next_test_ip4();
+        let listener = TcpListener::bind_with_backlog(addr, 5).unwrap();
+        assert_eq!(listener.backlog(), 5);
+        let mut acceptor = listener.listen().unwrap();
+
+        spawn(move|| {
+            let _stream = TcpStream::connect(addr).unwrap();
+        });
+        acceptor.accept().unwrap();
+
+        assert_eq!(TcpListener::bind(next_test_ip4()).unwrap().backlog(), 128);
+    }
+
+    #[test]
+    fn bind_with_backlog_burst() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind_with_backlog(addr, 128).listen();
+        let n = 32u;
+
+        // Every connection is made before any of them is accepted.
+        let (tx, rx) = channel();
+        for _ in range(0, n) {
+            let tx = tx.clone();
+            spawn(move|| {
+                let mut s = TcpStream::connect(addr).unwrap();
+                s.write(&[1]).unwrap();
+                tx.send(());
+            });
+        }
+        for _ in range(0, n) { rx.recv(); }
+
+        for _ in range(0, n) {
+            let mut s = acceptor.accept().unwrap();
+            assert_eq!(s.read_byte(), Ok(1));
+        }
+    }
+
+    #[test]
+    fn accept_defaults() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        listener.set_accept_defaults(SocketOptions {
+            nodelay: true,
+            .. Default::default()
+        });
+        let mut acceptor = listener.listen().unwrap();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            assert_eq!(s.nodelay(), Ok(false));
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        assert_eq!(s.nodelay(), Ok(true));
+    }
+
+    #[test]
+    fn connect_deferred() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        // the connect proceeds while this task goes on to accept it
+        let pending = TcpStream::connect_deferred(addr);
+        let mut server = acceptor.accept().unwrap();
+        server.write(&[1]).unwrap();
+
+        let mut stream = pending.get().unwrap();
+        assert_eq!(stream.read_byte(), Ok(1));
+        assert_eq!(stream.peer_name(), Ok(addr));
+    }
+
+    #[test]
+    fn connect_deferred_error() {
+        let addr = next_test_ip4();
+        let pending = TcpStream::connect_deferred(addr);
+        assert_eq!(pending.get().err().unwrap().kind, ConnectionRefused);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn recv_lowat() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            stream.write(&[1, 2]).unwrap();
+            ::io::timer::sleep(Duration::milliseconds(50));
+            stream.write(&[3, 4]).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        stream.set_recv_lowat(4).unwrap();
+        tx.send(());
+        let mut buf = [0, ..8];
+        assert_eq!(stream.read(&mut buf), Ok(4));
+        assert_eq!(buf[..4], [1, 2, 3, 4][]);
+    }
+
+    #[test]
+    fn describe() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let _stream = acceptor.accept().unwrap();
+            rx.recv();
+        });
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.set_nodelay(true).unwrap();
+        let desc = stream.describe();
+        assert!(desc.contains(format!(":{}", addr.port)[]), "{}", desc);
+        assert!(desc.contains("nodelay: true"), "{}", desc);
+        tx.send(());
+    }
+
+    #[test]
+    fn read_deadline() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            stream.write(&[1, 2]).unwrap();
+            rx.recv();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        let mut buf = [0, ..10];
+        assert_eq!(stream.read_deadline(&mut buf, 10).err().unwrap().kind,
+                   TimedOut);
+
+        tx.send(());
+        let start = ::sys::timer::now();
+        assert_eq!(stream.read_deadline(&mut buf, 10000), Ok(2));
+        assert!(::sys::timer::now() - start < 5000);
+        assert_eq!(buf[..2], [1, 2][]);
+        tx.send(());
+    }
+
+    #[test]
+    fn write_deadline() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let _stream = TcpStream::connect(addr).unwrap();
+            // never read, so the writer fills up the socket buffers
+            rx.recv();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.write_deadline(&[0], 0).err().unwrap().kind, TimedOut);
+
+        let buf = Vec::from_elem(64 * 1024 * 1024, 0u8);
+        match stream.write_deadline(buf[], 100) {
+            Err(IoError { kind: ShortWrite(n), .. }) => {
+                assert!(n > 0 && n < buf.len());
+            }
+            r => panic!("expected a short write, got {}", r),
+        }
+        tx.send(());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn path_mtu() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let _stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        let mtu = stream.path_mtu().unwrap();
+        assert!(mtu >= 1280, "implausible mtu {}", mtu);
+        tx.send(());
+    }
+
+    #[test]
+    fn read_uninit() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(&[1, 2, 3]).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        let mut buf = [0xaa, ..16];
+        let mut got = Vec::new();
+        loop {
+            match stream.read_uninit(&mut buf) {
+                Ok(n) => got.push_all(buf[..n]),
+                Err(ref e) if e.kind == EndOfFile => break,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(got, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn userdata() {
+        #[deriving(PartialEq, Show)]
+        struct Session { id: uint }
+
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        spawn(move|| {
+            let _stream = TcpStream::connect(addr).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert!(stream.userdata::<Session>().is_none());
+        stream.set_userdata(Session { id: 3 });
+        assert_eq!(stream.userdata::<Session>(), Some(&mut Session { id: 3 }));
+        assert!(stream.userdata::<uint>().is_none());
+
+        stream.userdata::<Session>().unwrap().id = 4;
+        assert_eq!(stream.userdata::<Session>().unwrap().id, 4);
+        assert!(stream.clone().userdata::<Session>().is_none());
+    }
+
+    #[test]
+    fn accept_proxy_protocol_v1() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+                  .unwrap();
+            stream.write(b"hello").unwrap();
+        });
+
+        let (mut stream, client) = acceptor.accept_proxy_protocol(5000).unwrap();
+        assert_eq!(client, SocketAddr { ip: Ipv4Addr(192, 168, 0, 1), port: 56324 });
+        assert_eq!(stream.read_to_end().unwrap(), b"hello".to_vec());
+    }
+
+    #[test]
+    fn accept_proxy_protocol_v2() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c").unwrap();
+            stream.write(&[10, 0, 0, 1, 10, 0, 0, 2, 0x1f, 0x90, 0, 80]).unwrap();
+            stream.write(b"hello").unwrap();
+        });
+
+        let (mut stream, client) = acceptor.accept_proxy_protocol(5000).unwrap();
+        assert_eq!(client, SocketAddr { ip: Ipv4Addr(10, 0, 0, 1), port: 8080 });
+        assert_eq!(stream.read_to_end().unwrap(), b"hello".to_vec());
+    }
+
+    #[test]
+    fn accept_proxy_protocol_malformed() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(b"PROXY TCP4 not-an-address\r\nhello").unwrap();
+        });
+
+        match acceptor.accept_proxy_protocol(5000) {
+            Err(ref e) if e.kind == InvalidInput => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(..) => panic!("accepted a malformed header"),
+        }
+    }
+
+    #[test]
+    fn accept_proxy_protocol_family_mismatch() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(b"PROXY TCP6 192.168.0.1 192.168.0.11 56324 443\r\n")
+                  .unwrap();
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(b"PROXY TCP4 192.168.0.1 ::1 56324 443\r\n").unwrap();
+        });
+
+        for _ in range(0u, 2) {
+            match acceptor.accept_proxy_protocol(5000) {
+                Err(ref e) if e.kind == InvalidInput => {}
+                Err(e) => panic!("unexpected error {}", e),
+                Ok(..) => panic!("accepted a header with mismatched families"),
+            }
+        }
+    }
+
+    #[test]
+    fn accept_proxy_protocol_timeout() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        let (tx, rx) = channel::<()>();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(b"PROXY TCP4 192.168.0.1").unwrap();
+            let _ = rx.recv_opt();
+        });
+
+        match acceptor.accept_proxy_protocol(50) {
+            Err(ref e) if e.kind == TimedOut => {}
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(..) => panic!("accepted an incomplete header"),
+        }
+        drop(tx);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn defer_accept() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        listener.set_defer_accept(Some(5)).unwrap();
+        let mut acceptor = listener.listen().unwrap();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            stream.write(&[1]).unwrap();
+            rx.recv();
+        });
+
+        acceptor.set_timeout(Some(100));
+        assert_eq!(acceptor.accept().err().unwrap().kind, TimedOut);
+
+        tx.send(());
+        acceptor.set_timeout(None);
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.read_byte(), Ok(1));
+        tx.send(());
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn flush_acked() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            assert_eq!(stream.read_exact(4096).unwrap().len(), 4096);
+            rx.recv();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        stream.write(&[0, ..4096]).unwrap();
+        assert!(stream.flush_acked(5000));
+        assert_eq!(stream.send_queue_len(), Ok(0));
+        tx.send(());
+    }
+
+    #[test]
+    fn read_after_timeout_rearmed() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            stream.write(&[1]).unwrap();
+            rx.recv();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        stream.set_read_timeout(Some(20));
+        assert_eq!(stream.read_byte().err().unwrap().kind, TimedOut);
+
+        stream.set_read_timeout(Some(5000));
+        tx.send(());
+        assert_eq!(stream.read_byte(), Ok(1));
+        tx.send(());
+    }
+
+    #[test]
+    fn accept_logger() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        let (tx, rx) = channel();
+        listener.set_accept_logger(move |&mut: peer: IpAddr, at: u64| {
+            tx.send((peer, at));
+        });
+        let mut acceptor = listener.listen().unwrap();
+
+        spawn(move|| {
+            let _a = TcpStream::connect(addr).unwrap();
+            ::io::timer::sleep(Duration::milliseconds(50));
+            let _b = TcpStream::connect(addr).unwrap();
+        });
+
+        let _a = acceptor.accept().unwrap();
+        let _b = acceptor.accept().unwrap();
+        let (peer1, at1) = rx.recv();
+        let (peer2, at2) = rx.recv();
+        assert_eq!(peer1, addr.ip);
+        assert_eq!(peer2, addr.ip);
+        assert!(at1 < at2);
+    }
+
+    #[test]
+    fn poll_readable() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            stream.write(&[1, 2]).unwrap();
+            rx.recv();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert!(!stream.poll_readable(20));
+        tx.send(());
+        assert!(stream.poll_readable(5000));
+        assert!(stream.poll_readable(0));
+        assert_eq!(stream.read_exact(2), Ok(vec![1, 2]));
+        tx.send(());
+    }
+
+    #[test]
+    fn total_byte_limit() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        listener.set_total_byte_limit(10);
+        let mut acceptor = listener.listen().unwrap();
+
+        spawn(move|| {
+            let mut a = TcpStream::connect(addr).unwrap();
+            a.write(&[0, ..6]).unwrap();
+            let mut b = TcpStream::connect(addr).unwrap();
+            b.write(&[0, ..4]).unwrap();
+            let _c = TcpStream::connect(addr);
+        });
+
+        let mut a = acceptor.accept().unwrap();
+        assert_eq!(a.read_exact(6).unwrap().len(), 6);
+        let mut b = acceptor.accept().unwrap();
+        assert_eq!(b.read_exact(4).unwrap().len(), 4);
+        assert_eq!(acceptor.accept().err().unwrap().kind, ResourceUnavailable);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn from_raw_fd() {
+        use libc;
+        use os::unix::{AsRawFd, FromRawFd};
+
+        let addr = next_test_ip4();
+        let listener = TcpListener::bind(addr).unwrap();
+        let listener: TcpListener = unsafe {
+            FromRawFd::from_raw_fd(libc::dup(listener.as_raw_fd()))
+        };
+        let mut acceptor = listener.listen();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1]).unwrap();
+        });
+
+        let s = acceptor.accept().unwrap();
+        let mut s: TcpStream = unsafe {
+            FromRawFd::from_raw_fd(libc::dup(s.as_raw_fd()))
+        };
+        assert_eq!(s.read_byte(), Ok(1));
+    }
+
+    #[test]
+    fn write_mapped() {
+        use io::fs::{File, unlink};
+        use os::{mod, MemoryMap, MapReadable, MapFd};
+
+        #[cfg(not(windows))]
+        fn get_fd(file: &File) -> ::libc::c_int {
+            use os::unix::AsRawFd;
+            file.as_raw_fd()
+        }
+
+        #[cfg(windows)]
+        fn get_fd(file: &File) -> ::libc::HANDLE {
+            use os::windows::AsRawHandle;
+            file.as_raw_handle()
+        }
+
+        let path = os::tmpdir().join(format!("write_mapped-{}.tmp", next_test_port()));
+        let data = Vec::from_fn(4096, |i| i as u8);
+        File::create(&path).write(data[]).unwrap();
+
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            tx.send(stream.read_to_end().unwrap());
+        });
+
+        {
+            let file = File::open(&path).unwrap();
+            let map = MemoryMap::new(data.len(), &[MapReadable, MapFd(get_fd(&file))])
+                                .unwrap();
+            let mut stream = acceptor.accept().unwrap();
+            stream.write_mapped(&map).unwrap();
+        }
+        assert_eq!(rx.recv()[], data[]);
+        unlink(&path).unwrap();
+    }
+
+    #[test]
+    fn connect_address_list() {
+        let refused = next_test_ip4();
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        spawn(move|| {
+            let _s = acceptor.accept().unwrap();
+        });
+
+        // Each address is tried in turn until one of them accepts
+        let mut s = TcpStream::connect([refused, addr][]).unwrap();
+        assert_eq!(s.peer_name().unwrap(), addr);
+
+        // and if none of them do, the last error is returned
+        let err = TcpStream::connect([refused, refused][]).err().unwrap();
+        assert_eq!(err.kind, ConnectionRefused);
+    }
+
+    #[test]
+    fn connect_with_policy() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        spawn(move|| {
+            let _a = acceptor.accept().unwrap();
+            let _b = acceptor.accept().unwrap();
+        });
+
+        // localhost may also resolve to ::1, on which nothing is listening
+        let mut s = TcpStream::connect_with_policy(("localhost", addr.port),
+                                                   SourcePolicy::PreferIpv4).unwrap();
+        assert_eq!(s.peer_name().unwrap(), addr);
+
+        let local = Ipv4Addr(127, 0, 0, 1);
+        let mut s = TcpStream::connect_with_policy(addr, SourcePolicy::Bind(local))
+                              .unwrap();
+        assert_eq!(s.socket_name().unwrap().ip, local);
+    }
+
+    // Only fails this way on hosts without a route to the documentation
+    // network (those with a default route just time out)
+    #[ignore]
+    #[test]
+    fn connect_network_unreachable() {
+        let addr = SocketAddr { ip: Ipv4Addr(192, 0, 2, 1), port: 80 };
+        match TcpStream::connect_timeout(addr, Duration::seconds(1)) {
+            Err(ref e) if e.kind == NetworkUnreachable => {}
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(..) => panic!("connected to an unroutable address"),
+        }
+    }
+
+    #[test]
+    fn connect_with_policy_bind_error() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        acceptor.set_timeout(Some(100));
+
+        // 192.0.2.1 is reserved for documentation, so it's not assigned to
+        // any interface and the bind fails before anything is connected.
+        let local = Ipv4Addr(192, 0, 2, 1);
+        assert!(TcpStream::connect_with_policy(addr, SourcePolicy::Bind(local)).is_err());
+        assert_eq!(acceptor.accept().err().unwrap().kind, TimedOut);
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn bind_reuse_port() {
+        let addr = next_test_ip4();
+        let mut a1 = TcpListener::bind_reuse_port(addr).listen().unwrap();
+        let mut a2 = TcpListener::bind_reuse_port(addr).listen().unwrap();
+
+        let _clients: Vec<TcpStream> = range(0u, 20).map(|_| {
+            TcpStream::connect(addr).unwrap()
+        }).collect();
+
+        fn drain(a: &mut TcpAcceptor) -> uint {
+            a.set_timeout(Some(100));
+            let mut n = 0;
+            while a.accept().is_ok() { n += 1; }
+            n
+        }
+        let (n1, n2) = (drain(&mut a1), drain(&mut a2));
+        assert_eq!(n1 + n2, 20);
+        assert!(n1 > 0 && n2 > 0, "accepted {} and {}", n1, n2);
+    }
+
+    // Binding to a device needs CAP_NET_RAW on older kernels
+    #[cfg(target_os = "linux")]
+    #[ignore]
+    #[test]
+    fn bind_device() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind_device(addr, "lo").listen();
+
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1]).unwrap();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        assert_eq!(s.read_byte(), Ok(1));
+    }
+
+    #[test]
+    fn bind_device_error() {
+        let addr = next_test_ip4();
+        let err = TcpListener::bind_device(addr, "a very long interface name")
+                              .err().unwrap();
+        if cfg!(any(target_os = "linux", target_os = "android")) {
+            assert_eq!(err.kind, InvalidInput);
+        } else {
+            assert_eq!(err.kind, IoUnavailable);
+        }
+    }
+
+    #[test]
+    fn bind_family_v4() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind_family(addr.port, AddressFamily::V4).unwrap();
+        assert_eq!(listener.socket_name().unwrap(),
+                   SocketAddr { ip: Ipv4Addr(0, 0, 0, 0), port: addr.port });
+        let mut acceptor = listener.listen();
+
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write(&[99]).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.read_byte(), Ok(99));
+    }
+
+    #[test]
+    fn flush_nodelay() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            assert_eq!(stream.read_exact(2), Ok(vec![1, 2]));
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.nodelay(), Ok(false));
+        stream.flush_nodelay(&[1]).unwrap();
+        assert_eq!(stream.nodelay(), Ok(false));
+
+        stream.set_nodelay(true).unwrap();
+        stream.flush_nodelay(&[2]).unwrap();
+        assert_eq!(stream.nodelay(), Ok(true));
+    }
+
+    #[test]
+    fn cork() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        spawn(move|| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_cork(true).unwrap();
+            stream.write(b"header\r\n").unwrap();
+            stream.write(b"body").unwrap();
+            stream.set_cork(false).unwrap();
+            // uncorking with nothing held back is fine
+            stream.set_cork(false).unwrap();
+        });
+
+        let mut stream = acceptor.accept().unwrap();
+        assert_eq!(stream.read_to_end(), Ok(b"header\r\nbody".to_vec()));
+    }
+
+    // Without TCP_CORK, set_cork falls back to buffering in TcpStream
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    #[test]
+    fn cork_emulated() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_cork(true).unwrap();
+            s.write(b"head").unwrap();
+            tx1.send(());
+            rx2.recv();
+            // held data goes out ahead of writes which bypass the buffer
+            s.writev(&[b"er", b"\r\n"]).unwrap();
+            s.write(b"body").unwrap();
+            s.write(&[b'.', ..super::CORK_BUFFER_SIZE]).unwrap();
+            // and dropping the stream sends the rest
+            s.write(b"!").unwrap();
+        });
+
+        let mut s = acceptor.accept().unwrap();
+        rx1.recv();
+        // the first write is still being held back
+        s.set_read_timeout(Some(50));
+        assert_eq!(s.read_byte().err().unwrap().kind, TimedOut);
+        s.set_read_timeout(None);
+        tx2.send(());
+
+        let mut expected = b"header\r\nbody".to_vec();
+        expected.push_all(&[b'.', ..super::CORK_BUFFER_SIZE]);
+        expected.push(b'!');
+        assert_eq!(s.read_to_end(), Ok(expected));
+    }
+
+    #[test]
+    fn per_ip_limit() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        listener.set_per_ip_limit(1);
+        let mut acceptor = listener.listen().unwrap();
+
+        let mut c1 = TcpStream::connect(addr).unwrap();
+        let mut s1 = acceptor.accept().unwrap();
+
+        // The second connection from the same address is reset, and accept
+        // goes on waiting for another.
+        let mut c2 = TcpStream::connect(addr).unwrap();
+        acceptor.set_timeout(Some(100));
+        assert_eq!(acceptor.accept().err().unwrap().kind, TimedOut);
+        assert!(c2.read_byte().is_err());
+
+        // The first connection is unaffected.
+        s1.write(&[1]).unwrap();
+        assert_eq!(c1.read_byte().unwrap(), 1);
+
+        // Dropping it frees up its place.
+        drop(s1);
+        acceptor.set_timeout(None);
+        let _c3 = TcpStream::connect(addr).unwrap();
+        acceptor.accept().unwrap();
     }
 
     #[test]
-    fn multiple_connect_interleaved_greedy_schedule_ip4() {
+    fn try_accept() {
         let addr = next_test_ip4();
-        static MAX: int = 10;
-        let acceptor = TcpListener::bind(addr).listen();
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        assert!(acceptor.try_accept().unwrap().is_none());
 
-        spawn(move|| {
-            let mut acceptor = acceptor;
-            for (i, stream) in acceptor.incoming().enumerate().take(MAX as uint) {
-                // Start another task to handle the connection
-                spawn(move|| {
-                    let mut stream = stream;
-                    let mut buf = [0];
-                    stream.read(&mut buf).unwrap();
-                    assert!(buf[0] == i as u8);
-                    debug!("read");
-                });
+        let mut c = TcpStream::connect(addr).unwrap();
+        let mut s;
+        loop {
+            match acceptor.try_accept().unwrap() {
+                Some(stream) => { s = stream; break }
+                None => ::io::timer::sleep(Duration::milliseconds(10)),
             }
-        });
-
-        connect(0, addr);
+        }
+        c.write(&[1]).unwrap();
+        assert_eq!(s.read_byte().unwrap(), 1);
+        assert!(acceptor.try_accept().unwrap().is_none());
+    }
 
-        fn connect(i: int, addr: SocketAddr) {
-            if i == MAX { return }
+    #[test]
+    fn connect_reject_self() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
 
-            spawn(move|| {
-                debug!("connecting");
-                let mut stream = TcpStream::connect(addr);
-                // Connect again before writing
-                connect(i + 1, addr);
-                debug!("writing");
-                stream.write(&[i as u8]).unwrap();
-            });
-        }
+        let mut c = TcpStream::connect_reject_self(addr).unwrap();
+        assert!(!c.is_self_connect().unwrap());
+        let mut s = acceptor.accept().unwrap();
+        assert!(!s.is_self_connect().unwrap());
     }
 
     #[test]
-    fn multiple_connect_interleaved_greedy_schedule_ip6() {
-        let addr = next_test_ip6();
-        static MAX: int = 10;
-        let acceptor = TcpListener::bind(addr).listen();
+    fn read_to_end_into() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
 
+        let (tx, rx) = channel::<()>();
         spawn(move|| {
-            let mut acceptor = acceptor;
-            for (i, stream) in acceptor.incoming().enumerate().take(MAX as uint) {
-                // Start another task to handle the connection
-                spawn(move|| {
-                    let mut stream = stream;
-                    let mut buf = [0];
-                    stream.read(&mut buf).unwrap();
-                    assert!(buf[0] == i as u8);
-                    debug!("read");
-                });
-            }
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(b"abc").unwrap();
+            rx.recv();
+            s.write(b"def").unwrap();
         });
 
-        connect(0, addr);
+        let mut s = acceptor.accept().unwrap();
+        let mut buf = Vec::new();
+        assert!(!s.read_to_end_into(&mut buf, 100).unwrap());
+        assert_eq!(buf, b"abc".to_vec());
+        tx.send(());
+        assert!(s.read_to_end_into(&mut buf, 10000).unwrap());
+        assert_eq!(buf, b"abcdef".to_vec());
+    }
 
-        fn connect(i: int, addr: SocketAddr) {
-            if i == MAX { return }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn keepalive_config() {
+        let addr = next_test_ip4();
+        let _acceptor = TcpListener::bind(addr).listen();
 
-            spawn(move|| {
-                debug!("connecting");
-                let mut stream = TcpStream::connect(addr);
-                // Connect again before writing
-                connect(i + 1, addr);
-                debug!("writing");
-                stream.write(&[i as u8]).unwrap();
-            });
-        }
+        let mut s = TcpStream::connect(addr).unwrap();
+        assert_eq!(s.keepalive_config().unwrap(), None);
+        let cfg = KeepaliveConfig { idle: 30, interval: 5, count: 4 };
+        s.set_keepalive_config(cfg).unwrap();
+        assert_eq!(s.keepalive_config().unwrap(), Some(cfg));
+        s.set_keepalive(None).unwrap();
+        assert_eq!(s.keepalive_config().unwrap(), None);
     }
 
     #[test]
-    fn multiple_connect_interleaved_lazy_schedule_ip4() {
-        static MAX: int = 10;
+    fn set_nonblocking() {
         let addr = next_test_ip4();
-        let acceptor = TcpListener::bind(addr).listen();
+        let mut acceptor = TcpListener::bind(addr).listen();
 
+        let (tx, rx) = channel();
         spawn(move|| {
-            let mut acceptor = acceptor;
-            for stream in acceptor.incoming().take(MAX as uint) {
-                // Start another task to handle the connection
-                spawn(move|| {
-                    let mut stream = stream;
-                    let mut buf = [0];
-                    stream.read(&mut buf).unwrap();
-                    assert!(buf[0] == 99);
-                    debug!("read");
-                });
-            }
+            let mut c = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            c.write(&[1]).unwrap();
         });
 
-        connect(0, addr);
-
-        fn connect(i: int, addr: SocketAddr) {
-            if i == MAX { return }
+        let mut s = acceptor.accept().unwrap();
+        s.set_nonblocking(true);
+        let mut buf = [0];
+        assert_eq!(s.read(&mut buf).err().unwrap().kind, ResourceUnavailable);
 
-            spawn(move|| {
-                debug!("connecting");
-                let mut stream = TcpStream::connect(addr);
-                // Connect again before writing
-                connect(i + 1, addr);
-                debug!("writing");
-                stream.write(&[99]).unwrap();
-            });
-        }
+        s.set_nonblocking(false);
+        tx.send(());
+        assert_eq!(s.read(&mut buf), Ok(1));
+        assert_eq!(buf[0], 1);
     }
 
     #[test]
-    fn multiple_connect_interleaved_lazy_schedule_ip6() {
-        static MAX: int = 10;
-        let addr = next_test_ip6();
-        let acceptor = TcpListener::bind(addr).listen();
+    fn accept_nonblocking() {
+        let addr = next_test_ip4();
+        let mut listener = TcpListener::bind(addr).unwrap();
+        listener.set_accept_nonblocking(true);
+        let mut acceptor = listener.listen().unwrap();
 
+        let (tx, rx) = channel();
         spawn(move|| {
-            let mut acceptor = acceptor;
-            for stream in acceptor.incoming().take(MAX as uint) {
-                // Start another task to handle the connection
-                spawn(move|| {
-                    let mut stream = stream;
-                    let mut buf = [0];
-                    stream.read(&mut buf).unwrap();
-                    assert!(buf[0] == 99);
-                    debug!("read");
-                });
-            }
+            let mut c = TcpStream::connect(addr).unwrap();
+            rx.recv();
+            c.write(&[1]).unwrap();
+            rx.recv();
         });
 
-        connect(0, addr);
-
-        fn connect(i: int, addr: SocketAddr) {
-            if i == MAX { return }
+        let mut s = acceptor.accept().unwrap();
+        let mut buf = [0];
+        assert_eq!(s.read(&mut buf).err().unwrap().kind, ResourceUnavailable);
+        tx.send(());
 
-            spawn(move|| {
-                debug!("connecting");
-                let mut stream = TcpStream::connect(addr);
-                // Connect again before writing
-                connect(i + 1, addr);
-                debug!("writing");
-                stream.write(&[99]).unwrap();
-            });
+        // Once the data arrives the read goes through; wait for it without
+        // blocking on the stream itself.
+        let mut n = None;
+        while n.is_none() {
+            match s.read(&mut buf) {
+                Ok(nread) => n = Some(nread),
+                Err(ref e) if e.kind == ResourceUnavailable => {
+                    ::io::timer::sleep(Duration::milliseconds(10));
+                }
+                Err(e) => panic!("{}", e),
+            }
         }
+        assert_eq!(n, Some(1));
+        assert_eq!(buf[0], 1);
+        tx.send(());
     }
 
-    pub fn socket_name(addr: SocketAddr) {
-        let mut listener = TcpListener::bind(addr).unwrap();
-
-        // Make sure socket_name gives
-        // us the socket we binded to.
-        let so_name = listener.socket_name();
-        assert!(so_name.is_ok());
-        assert_eq!(addr, so_name.unwrap());
-    }
+    #[test]
+    fn copy_between_streams() {
+        let (addr1, addr2) = (next_test_ip4(), next_test_ip4());
+        let mut a1 = TcpListener::bind(addr1).listen();
+        let mut a2 = TcpListener::bind(addr2).listen();
 
-    pub fn peer_name(addr: SocketAddr) {
-        let acceptor = TcpListener::bind(addr).listen();
         spawn(move|| {
-            let mut acceptor = acceptor;
-            acceptor.accept().unwrap();
+            let mut s = TcpStream::connect(addr1).unwrap();
+            s.write(&[0, ..10000]).unwrap();
+        });
+        let (tx, rx) = channel();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr2).unwrap();
+            tx.send(s.read_to_end().unwrap());
         });
 
-        let stream = TcpStream::connect(addr);
-
-        assert!(stream.is_ok());
-        let mut stream = stream.unwrap();
-
-        // Make sure peer_name gives us the
-        // address/port of the peer we've
-        // connected to.
-        let peer_name = stream.peer_name();
-        assert!(peer_name.is_ok());
-        assert_eq!(addr, peer_name.unwrap());
+        let mut from = a1.accept().unwrap();
+        let mut to = a2.accept().unwrap();
+        assert_eq!(::io::util::copy(&mut from, &mut to), Ok(10000));
+        drop(to);
+        assert_eq!(rx.recv(), [0, ..10000].to_vec());
     }
 
     #[test]
-    fn socket_and_peer_name_ip4() {
-        peer_name(next_test_ip4());
-        socket_name(next_test_ip4());
-    }
+    fn buffered_lines() {
+        let addr = next_test_ip4();
+        let mut acceptor = TcpListener::bind(addr).listen();
 
-    #[test]
-    fn socket_and_peer_name_ip6() {
-        // FIXME: peer name is not consistent
-        //peer_name(next_test_ip6());
-        socket_name(next_test_ip6());
+        spawn(move|| {
+            let s = TcpStream::connect(addr).unwrap();
+            let mut w = BufferedWriter::new(s);
+            for i in range(0u, 3) {
+                w.write_line(format!("line {}", i)[]).unwrap();
+            }
+            // dropping the writer flushes what it buffered
+        });
+
+        let mut r = BufferedReader::new(acceptor.accept().unwrap());
+        let lines: Vec<String> = r.lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec!["line 0\n".to_string(),
+                               "line 1\n".to_string(),
+                               "line 2\n".to_string()]);
     }
 
     #[test]
-    fn partial_read() {
+    fn upgrade_buffered_stream() {
         let addr = next_test_ip4();
-        let (tx, rx) = channel();
+        let mut acceptor = TcpListener::bind(addr).listen();
+
         spawn(move|| {
-            let mut srv = TcpListener::bind(addr).listen().unwrap();
-            tx.send(());
-            let mut cl = srv.accept().unwrap();
-            cl.write(&[10]).unwrap();
-            let mut b = [0];
-            cl.read(&mut b).unwrap();
-            tx.send(());
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(b"220 ready\r\nSTARTTLS data").unwrap();
         });
 
-        rx.recv();
-        let mut c = TcpStream::connect(addr).unwrap();
-        let mut b = [0, ..10];
-        assert_eq!(c.read(&mut b), Ok(1));
-        c.write(&[1]).unwrap();
-        rx.recv();
+        // Read the plaintext greeting, which also buffers what follows it.
+        let mut s = BufferedStream::new(acceptor.accept().unwrap());
+        assert_eq!(s.read_line().unwrap(), "220 ready\r\n".to_string());
+
+        // Hand the raw stream over, keeping the data read ahead of the line.
+        let (mut s, mut rest) = s.into_inner_with_buffer();
+        rest.push_all(s.read_to_end().unwrap()[]);
+        assert_eq!(rest, b"STARTTLS data".to_vec());
     }
 
     #[test]
-    fn double_bind() {
+    fn connect_like() {
         let addr = next_test_ip4();
-        let listener = TcpListener::bind(addr).unwrap().listen();
-        assert!(listener.is_ok());
-        match TcpListener::bind(addr).listen() {
-            Ok(..) => panic!(),
-            Err(e) => {
-                assert!(e.kind == ConnectionRefused || e.kind == OtherIoError,
-                        "unknown error: {} {}", e, e.kind);
-            }
-        }
+        let mut acceptor = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let _a = acceptor.accept().unwrap();
+            let _b = acceptor.accept().unwrap();
+        });
+
+        let mut template = TcpStream::connect(addr).unwrap();
+        template.set_nodelay(true).unwrap();
+
+        let mut s = TcpStream::connect_like(addr, &mut template).unwrap();
+        assert_eq!(s.nodelay(), Ok(true));
+        assert_eq!(s.options(), template.options());
     }
 
     #[test]
@@ -1124,6 +4633,40 @@ mod test {
         assert_eq!(s.read_to_end(), Ok(vec!(1)));
     }
 
+    #[test]
+    fn close_write_half() {
+        let addr = next_test_ip4();
+        let mut a = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1]).unwrap();
+            s.close_write().unwrap();
+            // The reading half is still open
+            assert_eq!(s.read_byte(), Ok(2));
+        });
+
+        let mut c = a.accept().unwrap();
+        assert_eq!(c.read_byte(), Ok(1));
+        assert_eq!(c.read_byte().err().unwrap().kind, EndOfFile);
+        c.write(&[2]).unwrap();
+    }
+
+    #[test]
+    fn close_explicitly() {
+        let addr = next_test_ip4();
+        let mut a = TcpListener::bind(addr).listen().unwrap();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1]).unwrap();
+            s.close().unwrap();
+            assert!(s.write(&[2]).is_err());
+            assert_eq!(s.read_byte().err().unwrap().kind, EndOfFile);
+        });
+
+        let mut c = a.accept().unwrap();
+        assert_eq!(c.read_to_end().unwrap(), vec![1]);
+    }
+
     #[test]
     fn accept_timeout() {
         let addr = next_test_ip4();
@@ -1293,6 +4836,26 @@ mod test {
         }
     }
 
+    #[test]
+    fn read_timeout_partial() {
+        let addr = next_test_ip4();
+        let mut a = TcpListener::bind(addr).listen().unwrap();
+        let (tx, rx) = channel::<()>();
+        spawn(move|| {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write(&[1]).unwrap();
+            let _ = rx.recv_opt();
+        });
+
+        // A read which gets some data before the deadline returns it rather
+        // than timing out waiting for more.
+        let mut s = a.accept().unwrap();
+        s.set_read_timeout(Some(50));
+        assert_eq!(s.read(&mut [0, 0]), Ok(1));
+        assert_eq!(s.read(&mut [0, 0]).err().unwrap().kind, TimedOut);
+        tx.send(());
+    }
+
     #[test]
     fn write_timeouts() {
         let addr = next_test_ip6();
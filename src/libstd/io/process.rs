@@ -578,6 +578,7 @@ impl Process {
                 kind: io::InvalidInput,
                 desc: "invalid argument: can't kill an exited process",
                 detail: None,
+                os_error: None,
             })
         }
 
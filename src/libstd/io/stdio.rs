@@ -449,6 +449,7 @@ impl StdWriter {
                     kind: OtherIoError,
                     desc: "stream is not a tty",
                     detail: None,
+                    os_error: None,
                 })
             }
         }
@@ -471,6 +472,7 @@ impl StdWriter {
                     kind: OtherIoError,
                     desc: "stream is not a tty",
                     detail: None,
+                    os_error: None,
                 })
             }
         }
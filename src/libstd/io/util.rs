@@ -13,7 +13,11 @@
 use prelude::*;
 use cmp;
 use io;
+use io::net::tcp::TcpStream;
+use mem;
 use slice::bytes::MutableByteVector;
+use sys::timer;
+use u32;
 
 /// Wraps a `Reader`, limiting the number of bytes that can be read from it.
 pub struct LimitReader<R> {
@@ -235,16 +239,22 @@ impl<R: Reader, W: Writer> Reader for TeeReader<R, W> {
     }
 }
 
-/// Copies all data from a `Reader` to a `Writer`.
-pub fn copy<R: Reader, W: Writer>(r: &mut R, w: &mut W) -> io::IoResult<()> {
+/// Copies all data from a `Reader` to a `Writer`, returning the number of
+/// bytes copied once the reader reaches the end of its data.
+///
+/// Any other error from either side is returned straight away, and the data
+/// copied up to that point has already been written.
+pub fn copy<R: Reader, W: Writer>(r: &mut R, w: &mut W) -> io::IoResult<u64> {
     let mut buf = [0, ..super::DEFAULT_BUF_SIZE];
+    let mut total = 0;
     loop {
         let len = match r.read(&mut buf) {
             Ok(len) => len,
-            Err(ref e) if e.kind == io::EndOfFile => return Ok(()),
+            Err(ref e) if e.kind == io::EndOfFile => return Ok(total),
             Err(e) => return Err(e),
         };
         try!(w.write(buf[..len]));
+        total += len as u64;
     }
 }
 
@@ -277,6 +287,443 @@ impl<T: Iterator<u8>> Reader for IterReader<T> {
     }
 }
 
+/// Wraps a `Reader`, making each `read` wait until at least `min` bytes are
+/// available.
+///
+/// This batches up the output of a peer which sends its data a few bytes at
+/// a time. A `read` into a buffer smaller than `min` waits for the whole
+/// buffer to be filled instead. If the underlying reader reaches EOF, or
+/// times out (for example because of a timeout set on a `TcpStream`), after
+/// some bytes have been read, then those bytes are returned and the error is
+/// reported by the next `read`. The same applies once the timeout set with
+/// `set_timeout` has passed, or if the underlying reader keeps returning no
+/// data, in which case the error is `NoProgress`.
+pub struct MinChunkReader<R> {
+    inner: R,
+    min: uint,
+    timeout: Option<u64>,
+}
+
+impl<R: Reader> MinChunkReader<R> {
+    /// Creates a new `MinChunkReader` which returns at least `min` bytes
+    /// from each read.
+    pub fn new(r: R, min: uint) -> MinChunkReader<R> {
+        MinChunkReader { inner: r, min: min, timeout: None }
+    }
+
+    /// Sets the longest time, in milliseconds, that a single `read` waits for
+    /// `min` bytes to accumulate.
+    ///
+    /// Once it has passed, the bytes read so far are returned, or a
+    /// `TimedOut` error if there are none. The time is only checked when the
+    /// underlying reader returns, so a peer which sends nothing at all also
+    /// needs a timeout on the underlying reader. `None`, the default, waits
+    /// indefinitely.
+    #[experimental = "the timeout argument may change in type and value"]
+    pub fn set_timeout(&mut self, ms: Option<u64>) {
+        self.timeout = ms;
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref<'a>(&'a self) -> &'a R { &self.inner }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut<'a>(&'a mut self) -> &'a mut R { &mut self.inner }
+
+    /// Consumes the `MinChunkReader`, returning the underlying `Reader`.
+    pub fn into_inner(self) -> R { self.inner }
+}
+
+impl<R: Reader> Reader for MinChunkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+        let min = cmp::min(cmp::max(self.min, 1), buf.len());
+        let deadline = self.timeout.map(|ms| timer::now() + ms);
+        let mut read = 0;
+        let mut zeroes = 0;
+        while read < min {
+            match self.inner.read(buf[mut read..]) {
+                Ok(0) => {
+                    zeroes += 1;
+                    if zeroes >= super::NO_PROGRESS_LIMIT {
+                        if read > 0 { break }
+                        return Err(io::standard_error(io::NoProgress))
+                    }
+                }
+                Ok(n) => {
+                    read += n;
+                    zeroes = 0;
+                }
+                Err(ref e) if read > 0 && (e.kind == io::EndOfFile ||
+                                           e.kind == io::TimedOut) => break,
+                Err(e) => return Err(e),
+            }
+            match deadline {
+                Some(deadline) if read < min && timer::now() >= deadline => {
+                    if read > 0 { break }
+                    return Err(io::standard_error(io::TimedOut))
+                }
+                _ => {}
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// Wraps a `Reader`, removing the UTF-8 byte order mark (`EF BB BF`) from the
+/// start of its data, if there is one.
+///
+/// Data which does not begin with a byte order mark is passed through
+/// unchanged.
+pub struct BomStrippingReader<R> {
+    inner: R,
+    // Bytes read while looking for the mark which turned out not to be one
+    pending: Vec<u8>,
+    checked: bool,
+}
+
+impl<R: Reader> BomStrippingReader<R> {
+    /// Creates a new `BomStrippingReader` reading from `r`.
+    pub fn new(r: R) -> BomStrippingReader<R> {
+        BomStrippingReader { inner: r, pending: Vec::new(), checked: false }
+    }
+
+    /// Consumes the `BomStrippingReader`, returning the underlying `Reader`.
+    ///
+    /// Any bytes which have been read from the underlying reader but not yet
+    /// returned are lost.
+    pub fn into_inner(self) -> R { self.inner }
+
+    fn check(&mut self) -> io::IoResult<()> {
+        const BOM: &'static [u8] = &[0xef, 0xbb, 0xbf];
+        while self.pending.len() < BOM.len() &&
+              self.pending[] == BOM[..self.pending.len()] {
+            match self.inner.read_byte() {
+                Ok(b) => self.pending.push(b),
+                Err(ref e) if e.kind == io::EndOfFile => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.pending[] == BOM {
+            self.pending.clear();
+        }
+        self.checked = true;
+        Ok(())
+    }
+}
+
+impl<R: Reader> Reader for BomStrippingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+        if !self.checked {
+            try!(self.check());
+        }
+        if self.pending.len() == 0 {
+            return self.inner.read(buf);
+        }
+        let n = cmp::min(buf.len(), self.pending.len());
+        for (slot, b) in buf.iter_mut().zip(self.pending.iter()) {
+            *slot = *b;
+        }
+        self.pending = self.pending[n..].to_vec();
+        Ok(n)
+    }
+}
+
+/// Wraps a stream, panicking when it is used in a way which points to a bug
+/// in the protocol built on top of it.
+///
+/// Writing after the writing half of the stream has been closed panics, as
+/// does reading after the stream has already reported end of file twice. This
+/// is intended to be used while testing, to surface misuse which would
+/// otherwise misbehave silently.
+pub struct StrictStream<S> {
+    inner: S,
+    write_closed: bool,
+    eofs: uint,
+}
+
+impl<S> StrictStream<S> {
+    /// Creates a new `StrictStream` wrapping `s`.
+    pub fn new(s: S) -> StrictStream<S> {
+        StrictStream { inner: s, write_closed: false, eofs: 0 }
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref<'a>(&'a self) -> &'a S { &self.inner }
+
+    /// Gets a mutable reference to the underlying stream.
+    ///
+    /// Operations on the underlying stream are not checked.
+    pub fn get_mut<'a>(&'a mut self) -> &'a mut S { &mut self.inner }
+
+    /// Consumes the `StrictStream`, returning the underlying stream.
+    pub fn into_inner(self) -> S { self.inner }
+}
+
+impl StrictStream<TcpStream> {
+    /// Closes the writing half of the underlying stream. Any later write to
+    /// this `StrictStream` panics.
+    pub fn close_write(&mut self) -> io::IoResult<()> {
+        self.write_closed = true;
+        self.inner.close_write()
+    }
+}
+
+impl<S: Reader> Reader for StrictStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+        if self.eofs >= 2 {
+            panic!("read from a stream which already reported end of file twice");
+        }
+        let ret = self.inner.read(buf);
+        match ret {
+            Err(ref e) if e.kind == io::EndOfFile => self.eofs += 1,
+            _ => {}
+        }
+        ret
+    }
+}
+
+impl<S: Writer> Writer for StrictStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::IoResult<()> {
+        if self.write_closed {
+            panic!("write to a stream after close_write");
+        }
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::IoResult<()> { self.inner.flush() }
+}
+
+/// A registry of named counters, such as the client of a metrics exporter,
+/// which an `InstrumentedStream` reports its activity to.
+pub trait MetricsSink {
+    /// Adds `n` to the counter called `name`.
+    fn increment(&mut self, name: &str, n: u64);
+}
+
+/// Wraps a stream, counting the operations performed on it in a
+/// `MetricsSink`.
+///
+/// Each successful read increments the `read.ops` counter by one and the
+/// `read.bytes` counter by the number of bytes read, and each failed read
+/// increments `read.errors` along with a counter named after the kind of the
+/// error, such as `read.errors.EndOfFile`. Writes are counted the same way
+/// under `write.ops`, `write.bytes`, `write.errors` and so on.
+pub struct InstrumentedStream<S> {
+    inner: S,
+    sink: Box<MetricsSink + Send>,
+}
+
+impl<S> InstrumentedStream<S> {
+    /// Creates a new `InstrumentedStream` wrapping `s`, which reports to
+    /// `sink`.
+    pub fn new(s: S, sink: Box<MetricsSink + Send>) -> InstrumentedStream<S> {
+        InstrumentedStream { inner: s, sink: sink }
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref<'a>(&'a self) -> &'a S { &self.inner }
+
+    /// Gets a mutable reference to the underlying stream.
+    ///
+    /// Operations on the underlying stream are not counted.
+    pub fn get_mut<'a>(&'a mut self) -> &'a mut S { &mut self.inner }
+
+    /// Consumes the `InstrumentedStream`, returning the underlying stream and
+    /// the sink.
+    pub fn into_inner(self) -> (S, Box<MetricsSink + Send>) {
+        (self.inner, self.sink)
+    }
+
+    fn record_error(&mut self, op: &str, e: &io::IoError) {
+        self.sink.increment(format!("{}.errors", op)[], 1);
+        self.sink.increment(format!("{}.errors.{}", op, e.kind)[], 1);
+    }
+}
+
+impl<S: Reader> Reader for InstrumentedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+        let ret = self.inner.read(buf);
+        match ret {
+            Ok(n) => {
+                self.sink.increment("read.ops", 1);
+                self.sink.increment("read.bytes", n as u64);
+            }
+            Err(ref e) => self.record_error("read", e),
+        }
+        ret
+    }
+}
+
+impl<S: Writer> Writer for InstrumentedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::IoResult<()> {
+        let ret = self.inner.write(buf);
+        match ret {
+            Ok(()) => {
+                self.sink.increment("write.ops", 1);
+                self.sink.increment("write.bytes", buf.len() as u64);
+            }
+            Err(ref e) => self.record_error("write", e),
+        }
+        ret
+    }
+
+    fn flush(&mut self) -> io::IoResult<()> { self.inner.flush() }
+}
+
+/// A pool of fixed-size read buffers which are recycled between reads.
+///
+/// Programs which read from many connections tend to allocate and free a
+/// buffer for every read. Reading through a `BufferPool` instead hands out a
+/// previously used buffer when one is available, and returns it to the pool
+/// once the `PooledBuf` holding the data is dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::MemReader;
+/// use std::io::util::BufferPool;
+///
+/// let mut pool = BufferPool::new(1024);
+/// let mut r = MemReader::new(vec![1, 2, 3]);
+/// {
+///     let buf = pool.read_from(&mut r).unwrap();
+///     assert_eq!(buf[], [1, 2, 3][]);
+/// } // the buffer goes back into the pool here
+/// ```
+pub struct BufferPool {
+    size: uint,
+    free: Vec<Vec<u8>>,
+}
+
+impl BufferPool {
+    /// Creates a new, initially empty, pool of buffers which are each `size`
+    /// bytes long.
+    pub fn new(size: uint) -> BufferPool {
+        BufferPool { size: size, free: Vec::new() }
+    }
+
+    /// Returns the number of idle buffers currently held by this pool.
+    pub fn idle(&self) -> uint { self.free.len() }
+
+    /// Takes a buffer from the pool, allocating a new one if none are idle,
+    /// and fills it with a single call to `read` on `r`.
+    ///
+    /// If the read fails, the buffer is immediately returned to the pool.
+    pub fn read_from<'a, R: Reader>(&'a mut self, r: &mut R)
+                                    -> io::IoResult<PooledBuf<'a>> {
+        let size = self.size;
+        let mut buf = self.free.pop().unwrap_or_else(|| Vec::from_elem(size, 0u8));
+        match r.read(buf[mut]) {
+            Ok(len) => Ok(PooledBuf { pool: self, buf: buf, len: len }),
+            Err(e) => { self.free.push(buf); Err(e) }
+        }
+    }
+}
+
+/// The data read by `BufferPool::read_from`.
+///
+/// This dereferences to the bytes which were read, and returns its buffer to
+/// the pool it came from when dropped.
+pub struct PooledBuf<'a> {
+    pool: &'a mut BufferPool,
+    buf: Vec<u8>,
+    len: uint,
+}
+
+impl<'a> Deref<[u8]> for PooledBuf<'a> {
+    fn deref(&self) -> &[u8] { self.buf[..self.len] }
+}
+
+#[unsafe_destructor]
+impl<'a> Drop for PooledBuf<'a> {
+    fn drop(&mut self) {
+        let buf = mem::replace(&mut self.buf, Vec::new());
+        self.pool.free.push(buf);
+    }
+}
+
+/// The default value of `FramedStream::max_frame_size`, 16 MiB.
+pub const DEFAULT_MAX_FRAME_SIZE: uint = 16 * 1024 * 1024;
+
+/// Wraps a stream to send and receive length-prefixed frames.
+///
+/// Each frame is written as its length, a big-endian `u32`, followed by that
+/// many bytes of payload.
+///
+/// Because the length of an incoming frame is chosen by the peer, frames
+/// larger than `max_frame_size` are rejected before any attempt is made to
+/// allocate space for, or read, their payload.
+pub struct FramedStream<S> {
+    inner: S,
+    /// The largest frame payload, in bytes, which `recv_frame` will accept.
+    pub max_frame_size: uint,
+}
+
+impl<S> FramedStream<S> {
+    /// Creates a new `FramedStream` wrapping `inner`, with the default
+    /// maximum frame size.
+    pub fn new(inner: S) -> FramedStream<S> {
+        FramedStream { inner: inner, max_frame_size: DEFAULT_MAX_FRAME_SIZE }
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S { &self.inner }
+
+    /// Gets a mutable reference to the underlying stream.
+    ///
+    /// # Warning
+    ///
+    /// Reading from or writing to the underlying stream directly is likely to
+    /// corrupt the framing.
+    pub fn get_mut(&mut self) -> &mut S { &mut self.inner }
+
+    /// Unwraps this `FramedStream`, returning the underlying stream.
+    pub fn into_inner(self) -> S { self.inner }
+}
+
+impl<S: Reader> FramedStream<S> {
+    /// Receives the next frame's payload.
+    ///
+    /// # Error
+    ///
+    /// If the length prefix of the incoming frame exceeds `max_frame_size`,
+    /// an `InvalidInput` error is returned and the payload is left unread in
+    /// the underlying stream. Errors from the underlying stream, including
+    /// `EndOfFile` in the middle of a frame, are returned as-is.
+    pub fn recv_frame(&mut self) -> io::IoResult<Vec<u8>> {
+        let len = try!(self.inner.read_be_u32()) as uint;
+        if len > self.max_frame_size {
+            return Err(io::IoError {
+                kind: io::InvalidInput,
+                desc: "frame too large",
+                detail: Some(format!("frame of {} bytes exceeds the maximum of {}",
+                                     len, self.max_frame_size)),
+                os_error: None,
+            })
+        }
+        self.inner.read_exact(len)
+    }
+}
+
+impl<S: Writer> FramedStream<S> {
+    /// Sends `buf` as a single frame.
+    ///
+    /// # Error
+    ///
+    /// Returns an `InvalidInput` error if `buf` is too long to be described by
+    /// the length prefix, in addition to any error from the underlying stream.
+    pub fn send_frame(&mut self, buf: &[u8]) -> io::IoResult<()> {
+        if buf.len() as u64 > u32::MAX as u64 {
+            return Err(io::standard_error(io::InvalidInput))
+        }
+        try!(self.inner.write_be_u32(buf.len() as u32));
+        self.inner.write(buf)
+    }
+
+    /// Flushes the underlying stream.
+    pub fn flush(&mut self) -> io::IoResult<()> { self.inner.flush() }
+}
+
 #[cfg(test)]
 mod test {
     use io::{MemReader, ByRefReader};
@@ -285,6 +732,51 @@ mod test {
     use super::*;
     use prelude::*;
 
+    #[test]
+    fn test_buffer_pool() {
+        let mut pool = BufferPool::new(4);
+        let mut r = MemReader::new(vec!(0, 1, 2, 3, 4, 5));
+
+        let first = {
+            let buf = pool.read_from(&mut r).unwrap();
+            assert_eq!(buf[], [0, 1, 2, 3][]);
+            buf.as_ptr()
+        };
+        assert_eq!(pool.idle(), 1);
+        {
+            let buf = pool.read_from(&mut r).unwrap();
+            assert_eq!(buf[], [4, 5][]);
+            assert_eq!(buf.as_ptr(), first);
+            assert_eq!(pool.idle(), 0);
+        }
+        assert!(pool.read_from(&mut r).is_err());
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[test]
+    fn test_framed_stream() {
+        let mut w = FramedStream::new(Vec::new());
+        w.send_frame(&[1, 2, 3]).unwrap();
+        w.send_frame(&[]).unwrap();
+
+        let mut r = FramedStream::new(MemReader::new(w.into_inner()));
+        assert_eq!(r.recv_frame().unwrap(), vec!(1, 2, 3));
+        assert_eq!(r.recv_frame().unwrap(), vec!());
+        assert_eq!(r.recv_frame().unwrap_err().kind, io::EndOfFile);
+    }
+
+    #[test]
+    fn test_framed_stream_max_frame_size() {
+        let mut w = FramedStream::new(Vec::new());
+        w.send_frame(&[0, ..100]).unwrap();
+
+        let mut r = FramedStream::new(MemReader::new(w.into_inner()));
+        r.max_frame_size = 10;
+        assert_eq!(r.recv_frame().unwrap_err().kind, io::InvalidInput);
+        // none of the payload should have been consumed
+        assert_eq!(r.into_inner().read_to_end().unwrap().len(), 100);
+    }
+
     #[test]
     fn test_limit_reader_unlimited() {
         let mut r = MemReader::new(vec!(0, 1, 2));
@@ -395,7 +887,7 @@ mod test {
     fn test_copy() {
         let mut r = MemReader::new(vec!(0, 1, 2, 3, 4));
         let mut w = Vec::new();
-        copy(&mut r, &mut w).unwrap();
+        assert_eq!(copy(&mut r, &mut w), Ok(5));
         assert_eq!(vec!(0, 1, 2, 3, 4), w);
     }
 
@@ -447,4 +939,131 @@ mod test {
         let mut buf = [];
         assert_eq!(Ok(0), r.read(&mut buf));
     }
+
+    #[test]
+    fn test_min_chunk_reader() {
+        let rs = range(0u8, 5).map(|i| MemReader::new(vec!(i)));
+        let mut r = MinChunkReader::new(ChainedReader::new(rs), 3);
+        let mut buf = [0, ..8];
+        assert_eq!(r.read(&mut buf), Ok(3));
+        assert!(buf[..3] == [0, 1, 2]);
+        assert_eq!(r.read(&mut buf), Ok(2));
+        assert!(buf[..2] == [3, 4]);
+        assert_eq!(r.read(&mut buf).unwrap_err().kind, io::EndOfFile);
+    }
+
+    #[test]
+    fn test_min_chunk_reader_no_progress() {
+        struct Zeroes;
+        impl Reader for Zeroes {
+            fn read(&mut self, _: &mut [u8]) -> io::IoResult<uint> { Ok(0) }
+        }
+
+        let mut r = MinChunkReader::new(Zeroes, 3);
+        let mut buf = [0, ..8];
+        assert_eq!(r.read(&mut buf).unwrap_err().kind, io::NoProgress);
+    }
+
+    #[test]
+    fn test_min_chunk_reader_timeout() {
+        // Sends one byte every 10ms
+        struct Dribble;
+        impl Reader for Dribble {
+            fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+                ::io::timer::sleep(::time::Duration::milliseconds(10));
+                buf[0] = 1;
+                Ok(1)
+            }
+        }
+
+        let mut r = MinChunkReader::new(Dribble, 1000);
+        r.set_timeout(Some(50));
+        let mut buf = [0, ..1000];
+        let n = r.read(&mut buf).unwrap();
+        assert!(n > 0 && n < 1000, "read {}", n);
+    }
+
+    #[test]
+    fn test_bom_stripping_reader() {
+        let r = MemReader::new(vec!(0xef, 0xbb, 0xbf, b'h', b'i'));
+        let mut r = BomStrippingReader::new(r);
+        assert_eq!(r.read_to_end().unwrap(), vec!(b'h', b'i'));
+
+        let r = MemReader::new(vec!(0xef, 0xbb, b'h', b'i'));
+        let mut r = BomStrippingReader::new(r);
+        assert_eq!(r.read_to_end().unwrap(), vec!(0xef, 0xbb, b'h', b'i'));
+
+        let r = MemReader::new(vec!(b'h', b'i'));
+        let mut r = BomStrippingReader::new(r);
+        assert_eq!(r.read_to_end().unwrap(), vec!(b'h', b'i'));
+
+        let mut r = BomStrippingReader::new(MemReader::new(vec!(0xef)));
+        assert_eq!(r.read_to_end().unwrap(), vec!(0xef));
+    }
+
+    #[test]
+    fn test_strict_stream() {
+        let mut s = StrictStream::new(MemReader::new(vec!(0, 1)));
+        assert_eq!(s.read_to_end().unwrap(), vec!(0, 1));
+        assert_eq!(s.read(&mut [0]).unwrap_err().kind, io::EndOfFile);
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_strict_stream_read_after_eof() {
+        let mut s = StrictStream::new(MemReader::new(vec!()));
+        let _ = s.read(&mut [0]);
+        let _ = s.read(&mut [0]);
+        let _ = s.read(&mut [0]);
+    }
+
+    #[test]
+    fn test_instrumented_stream() {
+        use collections::HashMap;
+        use sync::{Arc, Mutex};
+
+        struct Counters(Arc<Mutex<HashMap<String, u64>>>);
+        impl MetricsSink for Counters {
+            fn increment(&mut self, name: &str, n: u64) {
+                let Counters(ref counters) = *self;
+                let mut counters = counters.lock();
+                let total = counters.get(name).map_or(0, |n| *n);
+                counters.insert(name.to_string(), total + n);
+            }
+        }
+        let counters = Arc::new(Mutex::new(HashMap::new()));
+        let get = |&: name: &str| counters.lock().get(name).map_or(0, |n| *n);
+
+        let sink = box Counters(counters.clone());
+        let mut w = InstrumentedStream::new(Vec::new(), sink);
+        w.write(b"hello").unwrap();
+        w.write(b" world").unwrap();
+        let (data, _) = w.into_inner();
+
+        let sink = box Counters(counters.clone());
+        let mut r = InstrumentedStream::new(MemReader::new(data), sink);
+        assert_eq!(r.read_to_end().unwrap(), b"hello world".to_vec());
+
+        assert_eq!(get("write.ops"), 2);
+        assert_eq!(get("write.bytes"), 11);
+        assert_eq!(get("read.bytes"), 11);
+        assert!(get("read.ops") >= 1);
+        assert_eq!(get("read.errors"), 1);
+        assert_eq!(get("read.errors.EndOfFile"), 1);
+        assert_eq!(get("write.errors"), 0);
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_strict_stream_write_after_close_write() {
+        use io::{Listener, TcpListener, TcpStream};
+        use io::test::next_test_ip4;
+
+        let addr = next_test_ip4();
+        let _acceptor = TcpListener::bind(addr).listen();
+        let mut s = StrictStream::new(TcpStream::connect(addr).unwrap());
+        s.write(&[0]).unwrap();
+        s.close_write().unwrap();
+        let _ = s.write(&[1]);
+    }
 }
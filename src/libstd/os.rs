@@ -155,6 +155,7 @@ pub fn getcwd() -> IoResult<Path> {
             kind: OtherIoError,
             desc: "GetCurrentDirectoryW returned invalid UTF-16",
             detail: None,
+            os_error: None,
         }),
     }
 }
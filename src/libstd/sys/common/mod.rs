@@ -33,6 +33,7 @@ pub fn eof() -> IoError {
         kind: io::EndOfFile,
         desc: "end of file",
         detail: None,
+        os_error: None,
     }
 }
 
@@ -41,6 +42,7 @@ pub fn timeout(desc: &'static str) -> IoError {
         kind: io::TimedOut,
         desc: desc,
         detail: None,
+        os_error: None,
     }
 }
 
@@ -49,6 +51,7 @@ pub fn short_write(n: uint, desc: &'static str) -> IoError {
         kind: if n == 0 { io::TimedOut } else { io::ShortWrite(n) },
         desc: desc,
         detail: None,
+        os_error: None,
     }
 }
 
@@ -57,6 +60,7 @@ pub fn unimpl() -> IoError {
         kind: io::IoUnavailable,
         desc: "operations not yet supported",
         detail: None,
+        os_error: None,
     }
 }
 
@@ -94,6 +98,11 @@ pub trait AsInner<Inner> {
     fn as_inner(&self) -> &Inner;
 }
 
+// A trait for building std::io types from their representations
+pub trait FromInner<Inner> {
+    fn from_inner(inner: Inner) -> Self;
+}
+
 pub trait ProcessConfig<K: BytesContainer, V: BytesContainer> {
     fn program(&self) -> &CString;
     fn args(&self) -> &[CString];
@@ -25,6 +25,7 @@ use sys::{mod, retry, c, sock_t, last_error, last_net_error, last_gai_error, clo
 use sync::{Mutex, MutexGuard};
 use sys_common::{mod, keep_going, short_write, timeout};
 use prelude::*;
+use thunk::Thunk;
 use cmp;
 use io;
 
@@ -209,6 +210,7 @@ pub fn sockaddr_to_addr(storage: &libc::sockaddr_storage,
                 kind: io::InvalidInput,
                 desc: "invalid argument",
                 detail: None,
+                os_error: None,
             })
         }
     }
@@ -418,7 +420,7 @@ pub fn write<T, L, W>(fd: sock_t,
             // As with read(), first wait for the socket to be ready for
             // the I/O operation.
             match await(&[fd], deadline, Writable) {
-                Err(ref e) if e.kind == io::EndOfFile && written > 0 => {
+                Err(ref e) if e.kind == io::TimedOut && written > 0 => {
                     assert!(deadline.is_some());
                     return Err(short_write(written, "short write"))
                 }
@@ -498,7 +500,9 @@ pub fn connect_timeout(fd: sock_t,
             // Recalculate the timeout each iteration (it is generally
             // undefined what the value of the 'tv' is after select
             // returns EINTR).
-            let mut tv = ms_to_timeval(timeout - (timer::now() - start));
+            let elapsed = timer::now() - start;
+            let left = if elapsed > timeout {0} else {timeout - elapsed};
+            let mut tv = ms_to_timeval(left);
             c::select(fd + 1, ptr::null_mut(), set as *mut _,
                       ptr::null_mut(), &mut tv)
         })
@@ -561,11 +565,15 @@ struct Inner {
     // Unused on Linux, where this lock is not necessary.
     #[allow(dead_code)]
     lock: Mutex<()>,
+
+    // Invoked at most once, the first time an operation on this socket
+    // observes that the connection has been dropped.
+    on_disconnect: Mutex<Option<Thunk<IoError>>>,
 }
 
 impl Inner {
     fn new(fd: sock_t) -> Inner {
-        Inner { fd: fd, lock: Mutex::new(()) }
+        Inner { fd: fd, lock: Mutex::new(()), on_disconnect: Mutex::new(None) }
     }
 }
 
@@ -589,19 +597,48 @@ impl<'a> Drop for Guard<'a> {
 // TCP streams
 ////////////////////////////////////////////////////////////////////////////////
 
+fn is_disconnect(e: &IoError, deadline: u64) -> bool {
+    match e.kind {
+        io::ConnectionReset | io::ConnectionAborted | io::BrokenPipe => true,
+        // Without a deadline, a timeout can only have come from the OS, e.g.
+        // from unanswered keepalive probes.
+        io::TimedOut => deadline == 0,
+        _ => false,
+    }
+}
+
 pub struct TcpStream {
     inner: Arc<Inner>,
     read_deadline: u64,
     write_deadline: u64,
+    nonblocking: bool,
 }
 
 impl TcpStream {
     pub fn connect(addr: SocketAddr, timeout: Option<u64>) -> IoResult<TcpStream> {
+        TcpStream::connect_from(addr, None, timeout)
+    }
+
+    pub fn connect_from(addr: SocketAddr, local: Option<IpAddr>,
+                        timeout: Option<u64>) -> IoResult<TcpStream> {
         sys::init_net();
 
         let fd = try!(socket(addr, libc::SOCK_STREAM));
         let ret = TcpStream::new(fd);
 
+        match local {
+            Some(ip) => {
+                let mut storage = unsafe { mem::zeroed() };
+                let len = addr_to_sockaddr(SocketAddr { ip: ip, port: 0 }, &mut storage);
+                let addrp = &storage as *const _ as *const libc::sockaddr;
+                match unsafe { libc::bind(fd, addrp, len) } {
+                    -1 => return Err(last_net_error()),
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+
         let mut storage = unsafe { mem::zeroed() };
         let len = addr_to_sockaddr(addr, &mut storage);
         let addrp = &storage as *const _ as *const libc::sockaddr;
@@ -625,16 +662,47 @@ impl TcpStream {
             inner: Arc::new(Inner::new(fd)),
             read_deadline: 0,
             write_deadline: 0,
+            nonblocking: false,
         }
     }
 
     pub fn fd(&self) -> sock_t { self.inner.fd }
 
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+    }
+
+    // A nonblocking stream may not wait at all, so its reads and writes are
+    // performed as if their deadline had already passed, and the resulting
+    // timeouts are reported as the operation having been unable to proceed.
+    fn deadline(&self, deadline: u64) -> u64 {
+        if self.nonblocking { timer::now() } else { deadline }
+    }
+    fn check_would_block<T>(&self, ret: IoResult<T>) -> IoResult<T> {
+        match ret {
+            Err(ref e) if self.nonblocking && e.kind == io::TimedOut => {
+                Err(IoError {
+                    kind: io::ResourceUnavailable,
+                    desc: "operation would block",
+                    detail: None,
+                    os_error: None,
+                })
+            }
+            ret => ret,
+        }
+    }
+
     pub fn set_nodelay(&mut self, nodelay: bool) -> IoResult<()> {
         setsockopt(self.fd(), libc::IPPROTO_TCP, libc::TCP_NODELAY,
                    nodelay as libc::c_int)
     }
 
+    pub fn nodelay(&mut self) -> IoResult<bool> {
+        let on: libc::c_int = try!(getsockopt(self.fd(), libc::IPPROTO_TCP,
+                                              libc::TCP_NODELAY));
+        Ok(on != 0)
+    }
+
     pub fn set_keepalive(&mut self, seconds: Option<uint>) -> IoResult<()> {
         let ret = setsockopt(self.fd(), libc::SOL_SOCKET, libc::SO_KEEPALIVE,
                              seconds.is_some() as libc::c_int);
@@ -644,6 +712,69 @@ impl TcpStream {
         }
     }
 
+    pub fn set_linger(&mut self, seconds: Option<uint>) -> IoResult<()> {
+        let linger = c::linger {
+            l_onoff: seconds.is_some() as c::linger_t,
+            l_linger: seconds.unwrap_or(0) as c::linger_t,
+        };
+        setsockopt(self.fd(), libc::SOL_SOCKET, c::SO_LINGER, linger)
+    }
+
+    pub fn set_ttl(&mut self, ttl: int) -> IoResult<()> {
+        let (level, opt) = match try!(self.socket_name()).ip {
+            Ipv4Addr(..) => (libc::IPPROTO_IP, libc::IP_TTL),
+            Ipv6Addr(..) => (libc::IPPROTO_IPV6, c::IPV6_UNICAST_HOPS),
+        };
+        setsockopt(self.fd(), level, opt, ttl as libc::c_int)
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_cork(&mut self, on: bool) -> IoResult<()> {
+        setsockopt(self.fd(), libc::IPPROTO_TCP, c::TCP_CORK,
+                   on as libc::c_int)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn set_cork(&mut self, _on: bool) -> IoResult<()> {
+        Err(sys_common::unimpl())
+    }
+
+    pub fn set_recv_lowat(&mut self, bytes: uint) -> IoResult<()> {
+        setsockopt(self.fd(), libc::SOL_SOCKET, c::SO_RCVLOWAT,
+                   bytes as libc::c_int)
+    }
+
+    pub fn set_recv_buffer_size(&mut self, bytes: uint) -> IoResult<()> {
+        setsockopt(self.fd(), libc::SOL_SOCKET, c::SO_RCVBUF,
+                   bytes as libc::c_int)
+    }
+
+    pub fn set_send_buffer_size(&mut self, bytes: uint) -> IoResult<()> {
+        setsockopt(self.fd(), libc::SOL_SOCKET, c::SO_SNDBUF,
+                   bytes as libc::c_int)
+    }
+
+    pub fn recv_buffer_size(&mut self) -> IoResult<uint> {
+        let n: libc::c_int = try!(getsockopt(self.fd(), libc::SOL_SOCKET,
+                                             c::SO_RCVBUF));
+        Ok(n as uint)
+    }
+
+    pub fn send_buffer_size(&mut self) -> IoResult<uint> {
+        let n: libc::c_int = try!(getsockopt(self.fd(), libc::SOL_SOCKET,
+                                             c::SO_SNDBUF));
+        Ok(n as uint)
+    }
+
+    pub fn keepalive(&mut self) -> IoResult<Option<uint>> {
+        let on: libc::c_int = try!(getsockopt(self.fd(), libc::SOL_SOCKET,
+                                              libc::SO_KEEPALIVE));
+        if on == 0 {
+            Ok(None)
+        } else {
+            self.tcp_keepalive().map(|n| Some(n))
+        }
+    }
+
     #[cfg(any(target_os = "macos", target_os = "ios"))]
     fn set_tcp_keepalive(&mut self, seconds: uint) -> IoResult<()> {
         setsockopt(self.fd(), libc::IPPROTO_TCP, libc::TCP_KEEPALIVE,
@@ -654,14 +785,88 @@ impl TcpStream {
         setsockopt(self.fd(), libc::IPPROTO_TCP, libc::TCP_KEEPIDLE,
                    seconds as libc::c_int)
     }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_tcp_keepalive(&mut self, seconds: uint) -> IoResult<()> {
+        setsockopt(self.fd(), libc::IPPROTO_TCP, c::TCP_KEEPIDLE,
+                   seconds as libc::c_int)
+    }
     #[cfg(not(any(target_os = "macos",
                   target_os = "ios",
                   target_os = "freebsd",
-                  target_os = "dragonfly")))]
+                  target_os = "dragonfly",
+                  target_os = "linux",
+                  target_os = "android")))]
     fn set_tcp_keepalive(&mut self, _seconds: uint) -> IoResult<()> {
         Ok(())
     }
 
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn tcp_keepalive(&mut self) -> IoResult<uint> {
+        let n: libc::c_int = try!(getsockopt(self.fd(), libc::IPPROTO_TCP,
+                                             libc::TCP_KEEPALIVE));
+        Ok(n as uint)
+    }
+    #[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+    fn tcp_keepalive(&mut self) -> IoResult<uint> {
+        let n: libc::c_int = try!(getsockopt(self.fd(), libc::IPPROTO_TCP,
+                                             libc::TCP_KEEPIDLE));
+        Ok(n as uint)
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn tcp_keepalive(&mut self) -> IoResult<uint> {
+        let n: libc::c_int = try!(getsockopt(self.fd(), libc::IPPROTO_TCP,
+                                             c::TCP_KEEPIDLE));
+        Ok(n as uint)
+    }
+    #[cfg(not(any(target_os = "macos",
+                  target_os = "ios",
+                  target_os = "freebsd",
+                  target_os = "dragonfly",
+                  target_os = "linux",
+                  target_os = "android")))]
+    fn tcp_keepalive(&mut self) -> IoResult<uint> {
+        Err(sys_common::unimpl())
+    }
+
+    #[cfg(any(target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "linux",
+              target_os = "android"))]
+    pub fn set_keepalive_probes(&mut self, interval: uint,
+                                count: uint) -> IoResult<()> {
+        try!(setsockopt(self.fd(), libc::IPPROTO_TCP, c::TCP_KEEPINTVL,
+                        interval as libc::c_int));
+        setsockopt(self.fd(), libc::IPPROTO_TCP, c::TCP_KEEPCNT,
+                   count as libc::c_int)
+    }
+    #[cfg(not(any(target_os = "freebsd",
+                  target_os = "dragonfly",
+                  target_os = "linux",
+                  target_os = "android")))]
+    pub fn set_keepalive_probes(&mut self, _interval: uint,
+                                _count: uint) -> IoResult<()> {
+        Err(sys_common::unimpl())
+    }
+
+    #[cfg(any(target_os = "freebsd",
+              target_os = "dragonfly",
+              target_os = "linux",
+              target_os = "android"))]
+    pub fn keepalive_probes(&mut self) -> IoResult<(uint, uint)> {
+        let interval: libc::c_int = try!(getsockopt(self.fd(), libc::IPPROTO_TCP,
+                                                    c::TCP_KEEPINTVL));
+        let count: libc::c_int = try!(getsockopt(self.fd(), libc::IPPROTO_TCP,
+                                                 c::TCP_KEEPCNT));
+        Ok((interval as uint, count as uint))
+    }
+    #[cfg(not(any(target_os = "freebsd",
+                  target_os = "dragonfly",
+                  target_os = "linux",
+                  target_os = "android")))]
+    pub fn keepalive_probes(&mut self) -> IoResult<(uint, uint)> {
+        Err(sys_common::unimpl())
+    }
+
     #[cfg(target_os = "linux")]
     fn lock_nonblocking(&self) {}
 
@@ -685,7 +890,125 @@ impl TcpStream {
                        buf.len() as wrlen,
                        flags) as libc::c_int
         };
-        read(fd, self.read_deadline, dolock, doread)
+        let deadline = self.deadline(self.read_deadline);
+        let ret = read(fd, deadline, dolock, doread);
+        self.check_would_block(self.check_disconnect(ret, deadline))
+    }
+
+    pub fn read_deadline(&mut self, buf: &mut [u8], timeout: u64) -> IoResult<uint> {
+        let fd = self.fd();
+        let dolock = |&:| self.lock_nonblocking();
+        let doread = |&mut: nb| unsafe {
+            let flags = if nb {c::MSG_DONTWAIT} else {0};
+            libc::recv(fd,
+                       buf.as_mut_ptr() as *mut libc::c_void,
+                       buf.len() as wrlen,
+                       flags) as libc::c_int
+        };
+        let deadline = timer::now() + timeout;
+        let ret = read(fd, deadline, dolock, doread);
+        self.check_disconnect(ret, deadline)
+    }
+
+    pub fn peek(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let fd = self.fd();
+        let dolock = |&:| self.lock_nonblocking();
+        let doread = |&mut: nb| unsafe {
+            let flags = if nb {c::MSG_DONTWAIT} else {0};
+            libc::recv(fd,
+                       buf.as_mut_ptr() as *mut libc::c_void,
+                       buf.len() as wrlen,
+                       flags | c::MSG_PEEK) as libc::c_int
+        };
+        let deadline = self.deadline(self.read_deadline);
+        let ret = read(fd, deadline, dolock, doread);
+        self.check_would_block(self.check_disconnect(ret, deadline))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_timestamping(&mut self, on: bool) -> IoResult<()> {
+        setsockopt(self.fd(), libc::SOL_SOCKET, c::SO_TIMESTAMP, on as libc::c_int)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn set_timestamping(&mut self, _on: bool) -> IoResult<()> {
+        Err(sys_common::unimpl())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn read_timestamped(&mut self, buf: &mut [u8])
+                            -> IoResult<(uint, Option<u64>)> {
+        let fd = self.fd();
+        // u64 elements keep the control buffer suitably aligned for cmsghdr
+        let mut control = [0u64, ..8];
+        let mut iov = c::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len() as libc::size_t,
+        };
+        let mut msg: c::msghdr = unsafe { mem::zeroed() };
+
+        let dolock = |&:| self.lock_nonblocking();
+        let deadline = self.deadline(self.read_deadline);
+        let ret = read(fd, deadline, dolock, |nb| unsafe {
+            let flags = if nb {c::MSG_DONTWAIT} else {0};
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = mem::size_of_val(&control) as libc::size_t;
+            c::recvmsg(fd, &mut msg, flags) as libc::c_int
+        });
+        let n = try!(self.check_would_block(self.check_disconnect(ret, deadline)));
+
+        // Walk the control messages looking for the kernel's timestamp
+        let align = mem::size_of::<libc::size_t>();
+        let hdrlen = (mem::size_of::<c::cmsghdr>() + align - 1) & !(align - 1);
+        let base = control.as_ptr() as *const u8;
+        let mut offset = 0;
+        while offset + hdrlen <= msg.msg_controllen as uint {
+            let cmsg = unsafe { &*(base.offset(offset as int) as *const c::cmsghdr) };
+            if cmsg.cmsg_level == libc::SOL_SOCKET &&
+               cmsg.cmsg_type == c::SCM_TIMESTAMP {
+                let tv = unsafe {
+                    &*(base.offset((offset + hdrlen) as int) as *const libc::timeval)
+                };
+                let ns = tv.tv_sec as u64 * 1000000000 + tv.tv_usec as u64 * 1000;
+                return Ok((n, Some(ns)))
+            }
+            if cmsg.cmsg_len == 0 { break }
+            offset += (cmsg.cmsg_len as uint + align - 1) & !(align - 1);
+        }
+        Ok((n, None))
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_queue_len(&mut self) -> IoResult<uint> {
+        let mut n: libc::c_int = 0;
+        match unsafe { c::ioctl(self.fd(), c::SIOCOUTQ, &mut n) } {
+            -1 => Err(last_error()),
+            _ => Ok(n as uint),
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn send_queue_len(&mut self) -> IoResult<uint> {
+        Err(sys_common::unimpl())
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn path_mtu(&mut self) -> IoResult<uint> {
+        let (level, opt) = match try!(self.peer_name()).ip {
+            Ipv4Addr(..) => (libc::IPPROTO_IP, c::IP_MTU),
+            Ipv6Addr(..) => (libc::IPPROTO_IPV6, c::IPV6_MTU),
+        };
+        let mtu: libc::c_int = try!(getsockopt(self.fd(), level, opt));
+        Ok(mtu as uint)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn path_mtu(&mut self) -> IoResult<uint> {
+        Err(sys_common::unimpl())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn read_timestamped(&mut self, _buf: &mut [u8])
+                            -> IoResult<(uint, Option<u64>)> {
+        Err(sys_common::unimpl())
     }
 
     pub fn write(&mut self, buf: &[u8]) -> IoResult<()> {
@@ -698,8 +1021,204 @@ impl TcpStream {
                        len as wrlen,
                        flags) as i64
         };
-        write(fd, self.write_deadline, buf, true, dolock, dowrite).map(|_| ())
+        let deadline = self.deadline(self.write_deadline);
+        let ret = write(fd, deadline, buf, true, dolock, dowrite);
+        self.check_would_block(self.check_disconnect(ret, deadline)).map(|_| ())
     }
+
+    // The vectored calls can't be told not to block the way send() and
+    // recv() can, so they're only used while the stream has no timeout and
+    // isn't nonblocking. Otherwise (and on windows) each buffer is handed to
+    // read() or write() in turn, which honors those settings.
+    #[cfg(unix)]
+    pub fn writev(&mut self, bufs: &[&[u8]]) -> IoResult<()> {
+        if self.nonblocking || self.write_deadline != 0 {
+            return self.writev_sequential(bufs)
+        }
+        let fd = self.fd();
+        let total = bufs.iter().fold(0, |n, buf| n + buf.len());
+        let mut written = 0;
+        while written < total {
+            // Skip over whatever has been written so far
+            let mut skip = written;
+            let mut iov = Vec::with_capacity(bufs.len());
+            for &buf in bufs.iter() {
+                if skip >= buf.len() {
+                    skip -= buf.len();
+                    continue
+                }
+                iov.push(c::iovec {
+                    iov_base: buf[skip..].as_ptr() as *mut libc::c_void,
+                    iov_len: (buf.len() - skip) as libc::size_t,
+                });
+                skip = 0;
+            }
+            let ret = retry(|| unsafe {
+                c::writev(fd, iov.as_ptr(), iov.len() as libc::c_int)
+            });
+            // Another handle may have briefly made the socket nonblocking
+            if ret < 0 && wouldblock() {
+                try!(await(&[fd], None, Writable));
+                continue
+            }
+            if ret < 0 {
+                return self.check_disconnect(Err(last_net_error()), 0)
+            }
+            written += ret as uint;
+        }
+        Ok(())
+    }
+    #[cfg(windows)]
+    pub fn writev(&mut self, bufs: &[&[u8]]) -> IoResult<()> {
+        self.writev_sequential(bufs)
+    }
+
+    // Writes each buffer in turn. If one of them can't all be written in
+    // time, the error counts everything written so far, as a single write of
+    // all the data would.
+    fn writev_sequential(&mut self, bufs: &[&[u8]]) -> IoResult<()> {
+        let mut written = 0;
+        for buf in bufs.iter() {
+            match self.write(*buf) {
+                Ok(()) => written += buf.len(),
+                Err(e) => {
+                    let n = match e.kind {
+                        io::ShortWrite(n) => n,
+                        io::TimedOut | io::ResourceUnavailable => 0,
+                        _ => return Err(e),
+                    };
+                    if written + n == 0 { return Err(e) }
+                    return Err(short_write(written + n, "short write"))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    pub fn readv(&mut self, bufs: &mut [&mut [u8]]) -> IoResult<uint> {
+        if bufs.iter().all(|buf| buf.len() == 0) {
+            // readv() would return 0, which looks just like EOF
+            return Ok(0)
+        }
+        if self.nonblocking || self.read_deadline != 0 {
+            return self.readv_sequential(bufs)
+        }
+        let fd = self.fd();
+        let iov = bufs.iter_mut().map(|buf| {
+            c::iovec {
+                iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+                iov_len: buf.len() as libc::size_t,
+            }
+        }).collect::<Vec<c::iovec>>();
+        let ret;
+        loop {
+            match retry(|| unsafe {
+                c::readv(fd, iov.as_ptr(), iov.len() as libc::c_int)
+            }) {
+                // Another handle may have briefly made the socket nonblocking
+                -1 if wouldblock() => try!(await(&[fd], None, Readable)),
+                -1 => { ret = Err(last_net_error()); break }
+                0 => { ret = Err(sys_common::eof()); break }
+                n => { ret = Ok(n as uint); break }
+            }
+        }
+        self.check_disconnect(ret, 0)
+    }
+    #[cfg(windows)]
+    pub fn readv(&mut self, bufs: &mut [&mut [u8]]) -> IoResult<uint> {
+        self.readv_sequential(bufs)
+    }
+
+    // Reads into the first buffer with room in it, as a single read() is all
+    // that can be done without risking blocking after some data has arrived.
+    fn readv_sequential(&mut self, bufs: &mut [&mut [u8]]) -> IoResult<uint> {
+        match bufs.iter_mut().find(|buf| buf.len() > 0) {
+            Some(buf) => self.read(&mut **buf),
+            None => Ok(0),
+        }
+    }
+
+    pub fn write_deadline(&mut self, buf: &[u8], timeout: u64) -> IoResult<uint> {
+        if timeout == 0 {
+            return Err(sys_common::timeout("timed out"))
+        }
+        let fd = self.fd();
+        let deadline = timer::now() + timeout;
+        let dolock = |&:| self.lock_nonblocking();
+        let dowrite = |&: nb: bool, buf: *const u8, len: uint| unsafe {
+            let flags = if nb {c::MSG_DONTWAIT} else {0};
+            libc::send(fd,
+                       buf as *const _,
+                       len as wrlen,
+                       flags) as i64
+        };
+        let ret = write(fd, deadline, buf, true, dolock, dowrite);
+        self.check_disconnect(ret, deadline)
+    }
+
+    pub fn poll_readable(&mut self, timeout: u64) -> bool {
+        await(&[self.fd()], Some(timer::now() + timeout), Readable).is_ok()
+    }
+
+    pub fn probe_alive(&mut self) -> bool {
+        let fd = self.fd();
+        // If nothing is ready to be read then there's no news from the peer,
+        // good or bad, so the connection is presumed to still be alive.
+        match await(&[fd], Some(timer::now()), Readable) {
+            Ok(()) => {}
+            Err(..) => return true,
+        }
+
+        let mut buf = [0u8];
+        let _guard = self.lock_nonblocking();
+        let ret = retry(|| unsafe {
+            libc::recv(fd,
+                       buf.as_mut_ptr() as *mut libc::c_void,
+                       buf.len() as wrlen,
+                       c::MSG_PEEK | c::MSG_DONTWAIT) as libc::c_int
+        });
+        match ret {
+            // the peer has shut down its half of the connection
+            0 => false,
+            -1 if wouldblock() => true,
+            -1 => {
+                // let an on_disconnect callback know as well
+                let err = last_net_error();
+                let alive = !is_disconnect(&err, 0);
+                let _ = self.check_disconnect(Err::<(), _>(err), 0);
+                alive
+            }
+            _ => true,
+        }
+    }
+
+    pub fn on_disconnect(&mut self, cb: Thunk<IoError>) {
+        // If the connection already has a pending asynchronous error (such as
+        // a failed keepalive probe), report it right away.
+        let err: libc::c_int = getsockopt(self.fd(), libc::SOL_SOCKET,
+                                          libc::SO_ERROR).unwrap_or(0);
+        if err != 0 {
+            cb.invoke(decode_error_detailed(err));
+        } else {
+            *self.inner.on_disconnect.lock() = Some(cb);
+        }
+    }
+
+    fn check_disconnect<T>(&self, ret: IoResult<T>, deadline: u64) -> IoResult<T> {
+        match ret {
+            Err(ref e) if is_disconnect(e, deadline) => {
+                let cb = self.inner.on_disconnect.lock().take();
+                match cb {
+                    Some(cb) => cb.invoke(e.clone()),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+        ret
+    }
+
     pub fn peer_name(&mut self) -> IoResult<SocketAddr> {
         sockname(self.fd(), libc::getpeername)
     }
@@ -710,6 +1229,9 @@ impl TcpStream {
     pub fn close_read(&mut self) -> IoResult<()> {
         super::mkerr_libc(unsafe { libc::shutdown(self.fd(), libc::SHUT_RD) })
     }
+    pub fn close(&mut self) -> IoResult<()> {
+        super::mkerr_libc(unsafe { libc::shutdown(self.fd(), libc::SHUT_RDWR) })
+    }
 
     pub fn set_timeout(&mut self, timeout: Option<u64>) {
         let deadline = timeout.map(|a| timer::now() + a).unwrap_or(0);
@@ -722,18 +1244,28 @@ impl TcpStream {
     pub fn set_write_timeout(&mut self, timeout: Option<u64>) {
         self.write_deadline = timeout.map(|a| timer::now() + a).unwrap_or(0);
     }
+    pub fn read_timeout(&self) -> Option<u64> { remaining(self.read_deadline) }
+    pub fn write_timeout(&self) -> Option<u64> { remaining(self.write_deadline) }
 
     pub fn socket_name(&mut self) -> IoResult<SocketAddr> {
         sockname(self.fd(), libc::getsockname)
     }
 }
 
+// Converts a deadline into the number of milliseconds left until it.
+fn remaining(deadline: u64) -> Option<u64> {
+    if deadline == 0 { return None }
+    let now = timer::now();
+    Some(if deadline > now { deadline - now } else { 0 })
+}
+
 impl Clone for TcpStream {
     fn clone(&self) -> TcpStream {
         TcpStream {
             inner: self.inner.clone(),
             read_deadline: 0,
             write_deadline: 0,
+            nonblocking: self.nonblocking,
         }
     }
 }
@@ -35,6 +35,18 @@ pub const FIONBIO: libc::c_ulong = 0x5421;
           any(target_arch = "mips", target_arch = "mipsel")))]
 pub const FIONBIO: libc::c_ulong = 0x667e;
 
+// The number of bytes in a socket's send queue which the peer has not yet
+// acknowledged.
+#[cfg(any(all(target_os = "linux",
+              any(target_arch = "x86",
+                  target_arch = "x86_64",
+                  target_arch = "arm")),
+          target_os = "android"))]
+pub const SIOCOUTQ: libc::c_ulong = 0x5411;
+#[cfg(all(target_os = "linux",
+          any(target_arch = "mips", target_arch = "mipsel")))]
+pub const SIOCOUTQ: libc::c_ulong = 0x7472;
+
 #[cfg(any(target_os = "macos",
           target_os = "ios",
           target_os = "freebsd",
@@ -58,8 +70,122 @@ pub const MSG_DONTWAIT: libc::c_int = 0x80;
 #[cfg(any(target_os = "linux", target_os = "android"))]
 pub const MSG_DONTWAIT: libc::c_int = 0x40;
 
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          all(target_os = "linux",
+              any(target_arch = "mips", target_arch = "mipsel"))))]
+pub const SO_LINGER: libc::c_int = 0x0080;
+#[cfg(any(all(target_os = "linux",
+              any(target_arch = "x86",
+                  target_arch = "x86_64",
+                  target_arch = "arm")),
+          target_os = "android"))]
+pub const SO_LINGER: libc::c_int = 13;
+
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          all(target_os = "linux",
+              any(target_arch = "mips", target_arch = "mipsel"))))]
+pub const SO_RCVLOWAT: libc::c_int = 0x1004;
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          all(target_os = "linux",
+              any(target_arch = "mips", target_arch = "mipsel"))))]
+pub const SO_SNDBUF: libc::c_int = 0x1001;
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          all(target_os = "linux",
+              any(target_arch = "mips", target_arch = "mipsel"))))]
+pub const SO_RCVBUF: libc::c_int = 0x1002;
+#[cfg(any(all(target_os = "linux",
+              any(target_arch = "x86",
+                  target_arch = "x86_64",
+                  target_arch = "arm")),
+          target_os = "android"))]
+pub const SO_RCVLOWAT: libc::c_int = 18;
+#[cfg(any(all(target_os = "linux",
+              any(target_arch = "x86",
+                  target_arch = "x86_64",
+                  target_arch = "arm")),
+          target_os = "android"))]
+pub const SO_SNDBUF: libc::c_int = 7;
+#[cfg(any(all(target_os = "linux",
+              any(target_arch = "x86",
+                  target_arch = "x86_64",
+                  target_arch = "arm")),
+          target_os = "android"))]
+pub const SO_RCVBUF: libc::c_int = 8;
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly",
+          all(target_os = "linux",
+              any(target_arch = "mips", target_arch = "mipsel"))))]
+pub const SO_REUSEPORT: libc::c_int = 0x0200;
+#[cfg(any(all(target_os = "linux",
+              any(target_arch = "x86",
+                  target_arch = "x86_64",
+                  target_arch = "arm")),
+          target_os = "android"))]
+pub const SO_REUSEPORT: libc::c_int = 15;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const TCP_KEEPIDLE: libc::c_int = 4;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const TCP_KEEPINTVL: libc::c_int = 5;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const TCP_KEEPCNT: libc::c_int = 6;
+#[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+pub const TCP_KEEPINTVL: libc::c_int = 512;
+#[cfg(any(target_os = "freebsd", target_os = "dragonfly"))]
+pub const TCP_KEEPCNT: libc::c_int = 1024;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const TCP_DEFER_ACCEPT: libc::c_int = 9;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const TCP_CORK: libc::c_int = 3;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const SO_TIMESTAMP: libc::c_int = 29;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const SO_BINDTODEVICE: libc::c_int = 25;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const IFNAMSIZ: uint = 16;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const SCM_TIMESTAMP: libc::c_int = SO_TIMESTAMP;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const IP_MTU: libc::c_int = 14;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const IPV6_MTU: libc::c_int = 24;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub const IPV6_UNICAST_HOPS: libc::c_int = 16;
+#[cfg(any(target_os = "macos",
+          target_os = "ios",
+          target_os = "freebsd",
+          target_os = "dragonfly"))]
+pub const IPV6_UNICAST_HOPS: libc::c_int = 4;
+
+pub const MSG_PEEK: libc::c_int = 0x2;
+
 pub const WNOHANG: libc::c_int = 1;
 
+pub type linger_t = libc::c_int;
+
+#[repr(C)]
+pub struct linger {
+    pub l_onoff: linger_t,
+    pub l_linger: linger_t,
+}
+
 extern {
     pub fn gettimeofday(timeval: *mut libc::timeval,
                         tzp: *mut libc::c_void) -> libc::c_int;
@@ -74,6 +200,13 @@ extern {
                       optval: *mut libc::c_void,
                       optlen: *mut libc::socklen_t) -> libc::c_int;
     pub fn ioctl(fd: libc::c_int, req: libc::c_ulong, ...) -> libc::c_int;
+    pub fn readv(fd: libc::c_int, iov: *const iovec,
+                 iovcnt: libc::c_int) -> libc::ssize_t;
+    pub fn writev(fd: libc::c_int, iov: *const iovec,
+                  iovcnt: libc::c_int) -> libc::ssize_t;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn recvmsg(sockfd: libc::c_int, msg: *mut msghdr,
+                   flags: libc::c_int) -> libc::ssize_t;
 
 
     pub fn waitpid(pid: libc::pid_t, status: *mut libc::c_int,
@@ -88,6 +221,32 @@ extern {
     pub fn sigemptyset(set: *mut sigset_t) -> libc::c_int;
 }
 
+#[repr(C)]
+pub struct iovec {
+    pub iov_base: *mut libc::c_void,
+    pub iov_len: libc::size_t,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+pub struct msghdr {
+    pub msg_name: *mut libc::c_void,
+    pub msg_namelen: libc::socklen_t,
+    pub msg_iov: *mut iovec,
+    pub msg_iovlen: libc::size_t,
+    pub msg_control: *mut libc::c_void,
+    pub msg_controllen: libc::size_t,
+    pub msg_flags: libc::c_int,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[repr(C)]
+pub struct cmsghdr {
+    pub cmsg_len: libc::size_t,
+    pub cmsg_level: libc::c_int,
+    pub cmsg_type: libc::c_int,
+}
+
 #[cfg(any(target_os = "macos", target_os = "ios"))]
 mod select {
     pub const FD_SETSIZE: uint = 1024;
@@ -31,8 +31,9 @@
 
 #![experimental]
 
-use sys_common::AsInner;
+use sys_common::{AsInner, FromInner};
 use libc;
+use sys;
 
 use io;
 
@@ -99,9 +100,36 @@ impl AsRawFd for io::net::udp::UdpSocket {
     }
 }
 
+/// Build I/O objects from raw file descriptors
+pub trait FromRawFd {
+    /// Take ownership of the raw file descriptor `fd`, which is closed when
+    /// the returned object is dropped.
+    ///
+    /// This is unsafe because nothing checks that `fd` is open, is of the
+    /// right kind, or is not owned by anything else.
+    unsafe fn from_raw_fd(fd: Fd) -> Self;
+}
+
+// Sockets are kept in blocking mode when they aren't in use, so inherited ones
+// are put into the same state as those created by std.
+
+impl FromRawFd for io::net::tcp::TcpStream {
+    unsafe fn from_raw_fd(fd: Fd) -> io::net::tcp::TcpStream {
+        let _ = sys::set_nonblocking(fd, false);
+        FromInner::from_inner(sys::tcp::TcpStream::new(fd))
+    }
+}
+
+impl FromRawFd for io::net::tcp::TcpListener {
+    unsafe fn from_raw_fd(fd: Fd) -> io::net::tcp::TcpListener {
+        let _ = sys::set_nonblocking(fd, false);
+        FromInner::from_inner(sys::tcp::TcpListener::new(fd))
+    }
+}
+
 /// A prelude for conveniently writing platform-specific code.
 ///
 /// Includes all extension traits, and some important type definitions.
 pub mod prelude {
-    pub use super::{Fd, AsRawFd};
+    pub use super::{Fd, AsRawFd, FromRawFd};
 }
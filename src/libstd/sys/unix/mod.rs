@@ -76,7 +76,9 @@ extern "system" {
 pub fn last_gai_error(s: libc::c_int) -> IoError {
     use c_str::CString;
 
+    // getaddrinfo's error codes aren't errno values
     let mut err = decode_error(s);
+    err.os_error = None;
     err.detail = Some(unsafe {
         CString::new(gai_strerror(s), false).as_str().unwrap().to_string()
     });
@@ -95,6 +97,8 @@ pub fn decode_error(errno: i32) -> IoError {
         libc::EPIPE => (io::BrokenPipe, "broken pipe"),
         libc::ENOTCONN => (io::NotConnected, "not connected"),
         libc::ECONNABORTED => (io::ConnectionAborted, "connection aborted"),
+        libc::ENETUNREACH => (io::NetworkUnreachable, "network unreachable"),
+        libc::EHOSTUNREACH => (io::HostUnreachable, "host unreachable"),
         libc::EADDRNOTAVAIL => (io::ConnectionRefused, "address not available"),
         libc::EADDRINUSE => (io::ConnectionRefused, "address in use"),
         libc::ENOENT => (io::FileNotFound, "no such file or directory"),
@@ -115,7 +119,7 @@ pub fn decode_error(errno: i32) -> IoError {
 
         _ => (io::OtherIoError, "unknown error")
     };
-    IoError { kind: kind, desc: desc, detail: None }
+    IoError { kind: kind, desc: desc, detail: None, os_error: Some(errno) }
 }
 
 pub fn decode_error_detailed(errno: i32) -> IoError {
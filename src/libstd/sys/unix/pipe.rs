@@ -19,7 +19,7 @@ use prelude::*;
 use sys::{mod, timer, retry, c, set_nonblocking, wouldblock};
 use sys::fs::{fd_t, FileDesc};
 use sys_common::net::*;
-use sys_common::{eof, mkerr_libc};
+use sys_common::{mod, eof, mkerr_libc};
 
 fn unix_socket(ty: libc::c_int) -> IoResult<fd_t> {
     match unsafe { libc::socket(libc::AF_UNIX, ty, 0) } {
@@ -28,7 +28,14 @@ fn unix_socket(ty: libc::c_int) -> IoResult<fd_t> {
     }
 }
 
-fn addr_to_sockaddr_un(addr: &CString,
+// The name of a Unix socket: either a path in the filesystem, or (on Linux) a
+// name in the abstract namespace, which is not backed by any file.
+enum SocketName<'a> {
+    Path(&'a CString),
+    Abstract(&'a [u8]),
+}
+
+fn addr_to_sockaddr_un(addr: SocketName,
                        storage: &mut libc::sockaddr_storage)
                        -> IoResult<libc::socklen_t> {
     // the sun_path length is limited to SUN_LEN (with null)
@@ -36,20 +43,27 @@ fn addr_to_sockaddr_un(addr: &CString,
             mem::size_of::<libc::sockaddr_un>());
     let s = unsafe { &mut *(storage as *mut _ as *mut libc::sockaddr_un) };
 
-    let len = addr.len();
+    // Abstract names are marked by a leading null, and are not themselves
+    // null terminated, so either way one byte of sun_path is spoken for.
+    let (name, offset) = match addr {
+        SocketName::Path(path) => (path.as_bytes_no_nul(), 0),
+        SocketName::Abstract(name) => (name, 1),
+    };
+    let len = name.len();
     if len > s.sun_path.len() - 1 {
         return Err(IoError {
             kind: io::InvalidInput,
             desc: "invalid argument: path must be smaller than SUN_LEN",
             detail: None,
+            os_error: None,
         })
     }
     s.sun_family = libc::AF_UNIX as libc::sa_family_t;
-    for (slot, value) in s.sun_path.iter_mut().zip(addr.iter()) {
-        *slot = value;
+    for (slot, value) in s.sun_path[mut offset..].iter_mut().zip(name.iter()) {
+        *slot = *value as libc::c_char;
     }
 
-    // count the null terminator
+    // count the null terminator, or the leading null of an abstract name
     let len = mem::size_of::<libc::sa_family_t>() + len + 1;
     return Ok(len as libc::socklen_t);
 }
@@ -72,7 +86,7 @@ impl Drop for Inner {
     fn drop(&mut self) { unsafe { let _ = libc::close(self.fd); } }
 }
 
-fn connect(addr: &CString, ty: libc::c_int,
+fn connect(addr: SocketName, ty: libc::c_int,
            timeout: Option<u64>) -> IoResult<Inner> {
     let mut storage = unsafe { mem::zeroed() };
     let len = try!(addr_to_sockaddr_un(addr, &mut storage));
@@ -93,14 +107,24 @@ fn connect(addr: &CString, ty: libc::c_int,
     }
 }
 
-fn bind(addr: &CString, ty: libc::c_int) -> IoResult<Inner> {
+fn bind(addr: SocketName, ty: libc::c_int) -> IoResult<Inner> {
     let mut storage = unsafe { mem::zeroed() };
+    let is_path = match addr { SocketName::Path(..) => true, _ => false };
     let len = try!(addr_to_sockaddr_un(addr, &mut storage));
     let inner = Inner::new(try!(unix_socket(ty)));
     let addrp = &storage as *const _ as *const libc::sockaddr;
     match unsafe {
         libc::bind(inner.fd, addrp, len)
     } {
+        // Binding to a path fails this way if anything at all exists there,
+        // including a socket left behind by a listener which wasn't closed
+        -1 if is_path && sys::os::errno() == libc::EADDRINUSE as int => {
+            // keep the errno and its detail, only the kind is more specific
+            let mut err = super::last_error();
+            err.kind = io::PathAlreadyExists;
+            err.desc = "a file already exists at the socket path";
+            Err(err)
+        }
         -1 => Err(super::last_error()),
         _  => Ok(inner)
     }
@@ -119,10 +143,21 @@ pub struct UnixStream {
 impl UnixStream {
     pub fn connect(addr: &CString,
                    timeout: Option<u64>) -> IoResult<UnixStream> {
-        connect(addr, libc::SOCK_STREAM, timeout).map(|inner| {
+        connect(SocketName::Path(addr), libc::SOCK_STREAM, timeout).map(|inner| {
+            UnixStream::new(Arc::new(inner))
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn connect_abstract(name: &[u8]) -> IoResult<UnixStream> {
+        connect(SocketName::Abstract(name), libc::SOCK_STREAM, None).map(|inner| {
             UnixStream::new(Arc::new(inner))
         })
     }
+    #[cfg(not(target_os = "linux"))]
+    pub fn connect_abstract(_name: &[u8]) -> IoResult<UnixStream> {
+        Err(sys_common::unimpl())
+    }
 
     fn new(inner: Arc<Inner>) -> UnixStream {
         UnixStream {
@@ -211,15 +246,27 @@ impl Clone for UnixStream {
 
 pub struct UnixListener {
     inner: Inner,
-    path: CString,
+    // None for sockets in the abstract namespace
+    path: Option<CString>,
 }
 
 impl UnixListener {
     pub fn bind(addr: &CString) -> IoResult<UnixListener> {
-        bind(addr, libc::SOCK_STREAM).map(|fd| {
-            UnixListener { inner: fd, path: addr.clone() }
+        bind(SocketName::Path(addr), libc::SOCK_STREAM).map(|fd| {
+            UnixListener { inner: fd, path: Some(addr.clone()) }
+        })
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn bind_abstract(name: &[u8]) -> IoResult<UnixListener> {
+        bind(SocketName::Abstract(name), libc::SOCK_STREAM).map(|fd| {
+            UnixListener { inner: fd, path: None }
         })
     }
+    #[cfg(not(target_os = "linux"))]
+    pub fn bind_abstract(_name: &[u8]) -> IoResult<UnixListener> {
+        Err(sys_common::unimpl())
+    }
 
     pub fn fd(&self) -> fd_t { self.inner.fd }
 
@@ -313,8 +360,11 @@ impl Drop for UnixListener {
         // Unlink the path to the socket to ensure that it doesn't linger. We're
         // careful to unlink the path before we close the file descriptor to
         // prevent races where we unlink someone else's path.
-        unsafe {
-            let _ = libc::unlink(self.path.as_ptr());
+        match self.path {
+            Some(ref path) => unsafe {
+                let _ = libc::unlink(path.as_ptr());
+            },
+            None => {}
         }
     }
 }
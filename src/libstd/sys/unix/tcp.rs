@@ -9,7 +9,7 @@
 // except according to those terms.
 
 use io::net::ip;
-use io::IoResult;
+use io::{mod, IoResult, IoError};
 use libc;
 use mem;
 use ptr;
@@ -34,9 +34,29 @@ pub struct TcpListener {
 
 impl TcpListener {
     pub fn bind(addr: ip::SocketAddr) -> IoResult<TcpListener> {
+        TcpListener::bind_to(addr, None, false)
+    }
+
+    pub fn bind_device(addr: ip::SocketAddr, iface: &str) -> IoResult<TcpListener> {
+        TcpListener::bind_to(addr, Some(iface), false)
+    }
+
+    pub fn bind_reuse_port(addr: ip::SocketAddr) -> IoResult<TcpListener> {
+        TcpListener::bind_to(addr, None, true)
+    }
+
+    fn bind_to(addr: ip::SocketAddr, iface: Option<&str>,
+               reuse_port: bool) -> IoResult<TcpListener> {
         let fd = try!(socket(addr, libc::SOCK_STREAM));
         let ret = TcpListener { inner: FileDesc::new(fd, true) };
 
+        // The device has to be chosen first, so that the address is bound
+        // on that device.
+        match iface {
+            Some(iface) => try!(bind_to_device(fd, iface)),
+            None => {}
+        }
+
         let mut storage = unsafe { mem::zeroed() };
         let len = addr_to_sockaddr(addr, &mut storage);
         let addrp = &storage as *const _ as *const libc::sockaddr;
@@ -45,7 +65,10 @@ impl TcpListener {
         // to quickly rebind a socket, without needing to wait for
         // the OS to clean up the previous one.
         try!(setsockopt(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1 as libc::c_int));
-
+        if reuse_port {
+            try!(setsockopt(fd, libc::SOL_SOCKET, sys::c::SO_REUSEPORT,
+                            1 as libc::c_int));
+        }
 
         match unsafe { libc::bind(fd, addrp, len) } {
             -1 => Err(last_error()),
@@ -53,6 +76,10 @@ impl TcpListener {
         }
     }
 
+    pub fn new(fd: sock_t) -> TcpListener {
+        TcpListener { inner: FileDesc::new(fd, true) }
+    }
+
     pub fn fd(&self) -> sock_t { self.inner.fd() }
 
     pub fn listen(self, backlog: int) -> IoResult<TcpAcceptor> {
@@ -79,6 +106,38 @@ impl TcpListener {
     pub fn socket_name(&mut self) -> IoResult<ip::SocketAddr> {
         sockname(self.fd(), libc::getsockname)
     }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_defer_accept(&mut self, seconds: Option<uint>) -> IoResult<()> {
+        setsockopt(self.fd(), libc::IPPROTO_TCP, sys::c::TCP_DEFER_ACCEPT,
+                   seconds.unwrap_or(0) as libc::c_int)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn set_defer_accept(&mut self, _seconds: Option<uint>) -> IoResult<()> {
+        Err(sys_common::unimpl())
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_to_device(fd: sock_t, iface: &str) -> IoResult<()> {
+    // The name is passed NUL terminated, in a buffer of at most IFNAMSIZ
+    let mut name = [0u8, ..sys::c::IFNAMSIZ];
+    if iface.len() >= name.len() || iface.contains_char('\0') {
+        return Err(IoError {
+            kind: io::InvalidInput,
+            desc: "invalid network interface name",
+            detail: None,
+            os_error: None,
+        })
+    }
+    for (dst, src) in name.iter_mut().zip(iface.bytes()) {
+        *dst = src;
+    }
+    setsockopt(fd, libc::SOL_SOCKET, sys::c::SO_BINDTODEVICE, name)
+}
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_to_device(_fd: sock_t, _iface: &str) -> IoResult<()> {
+    Err(sys_common::unimpl())
 }
 
 pub struct TcpAcceptor {
@@ -128,6 +187,19 @@ impl TcpAcceptor {
         Err(sys_common::eof())
     }
 
+    pub fn try_accept(&mut self) -> IoResult<Option<TcpStream>> {
+        if self.inner.closed.load(atomic::SeqCst) {
+            return Err(sys_common::eof())
+        }
+        match retry(|| unsafe {
+            libc::accept(self.fd(), ptr::null_mut(), ptr::null_mut())
+        }) {
+            -1 if wouldblock() => Ok(None),
+            -1 => Err(last_net_error()),
+            fd => Ok(Some(TcpStream::new(fd as sock_t))),
+        }
+    }
+
     pub fn socket_name(&mut self) -> IoResult<ip::SocketAddr> {
         sockname(self.fd(), libc::getsockname)
     }
@@ -136,6 +208,14 @@ impl TcpAcceptor {
         self.deadline = timeout.map(|a| sys::timer::now() + a).unwrap_or(0);
     }
 
+    // Errors from accept() which leave the listening socket as it was, and
+    // which may well not happen again on the next call.
+    pub fn is_transient_error(errno: i32) -> bool {
+        use libc::consts::os::posix88::{ENFILE, ENOBUFS};
+        errno == libc::EMFILE || errno == ENFILE || errno == ENOBUFS ||
+            errno == libc::ENOMEM || errno == libc::ECONNABORTED
+    }
+
     pub fn close_accept(&mut self) -> IoResult<()> {
         self.inner.closed.store(true, atomic::SeqCst);
         let fd = FileDesc::new(self.inner.writer.fd(), false);
@@ -27,6 +27,7 @@ impl TTY {
                 kind: io::MismatchedFileTypeForOperation,
                 desc: "file descriptor is not a TTY",
                 detail: None,
+                os_error: None,
             })
         }
     }
@@ -22,6 +22,7 @@ pub const WSASYS_STATUS_LEN: uint = 128;
 pub const FIONBIO: libc::c_long = 0x8004667e;
 pub const FD_SETSIZE: uint = 64;
 pub const MSG_DONTWAIT: libc::c_int = 0;
+pub const MSG_PEEK: libc::c_int = 0x2;
 pub const ERROR_ILLEGAL_CHARACTER: libc::c_int = 582;
 pub const ENABLE_ECHO_INPUT: libc::DWORD = 0x4;
 pub const ENABLE_EXTENDED_FLAGS: libc::DWORD = 0x80;
@@ -73,6 +74,20 @@ pub type LPWSANETWORKEVENTS = *mut WSANETWORKEVENTS;
 
 pub type WSAEVENT = libc::HANDLE;
 
+pub const SO_LINGER: libc::c_int = 0x0080;
+pub const SO_RCVLOWAT: libc::c_int = 0x1004;
+pub const SO_SNDBUF: libc::c_int = 0x1001;
+pub const SO_RCVBUF: libc::c_int = 0x1002;
+pub const IPV6_UNICAST_HOPS: libc::c_int = 4;
+
+pub type linger_t = libc::c_ushort;
+
+#[repr(C)]
+pub struct linger {
+    pub l_onoff: linger_t,
+    pub l_linger: linger_t,
+}
+
 #[repr(C)]
 pub struct fd_set {
     fd_count: libc::c_uint,
@@ -16,8 +16,9 @@
 
 #![experimental]
 
-use sys_common::AsInner;
+use sys_common::{AsInner, FromInner};
 use libc;
+use sys;
 
 use io;
 
@@ -92,9 +93,38 @@ impl AsRawSocket for io::net::udp::UdpSocket {
     }
 }
 
+/// Build I/O objects from raw sockets.
+pub trait FromRawSocket {
+    /// Take ownership of the raw socket `sock`, which is closed when the
+    /// returned object is dropped.
+    ///
+    /// This is unsafe because nothing checks that `sock` is open, is of the
+    /// right kind, or is not owned by anything else.
+    unsafe fn from_raw_socket(sock: Socket) -> Self;
+}
+
+// Sockets are kept in blocking mode when they aren't in use, so inherited ones
+// are put into the same state as those created by std.
+
+impl FromRawSocket for io::net::tcp::TcpStream {
+    unsafe fn from_raw_socket(sock: Socket) -> io::net::tcp::TcpStream {
+        sys::init_net();
+        let _ = sys::set_nonblocking(sock, false);
+        FromInner::from_inner(sys::tcp::TcpStream::new(sock))
+    }
+}
+
+impl FromRawSocket for io::net::tcp::TcpListener {
+    unsafe fn from_raw_socket(sock: Socket) -> io::net::tcp::TcpListener {
+        sys::init_net();
+        let _ = sys::set_nonblocking(sock, false);
+        FromInner::from_inner(sys::tcp::TcpListener::new(sock))
+    }
+}
+
 /// A prelude for conveniently writing platform-specific code.
 ///
 /// Includes all extension traits, and some important type definitions.
 pub mod prelude {
-    pub use super::{Socket, Handle, AsRawSocket, AsRawHandle};
+    pub use super::{Socket, Handle, AsRawSocket, AsRawHandle, FromRawSocket};
 }
@@ -272,6 +272,7 @@ pub fn readdir(p: &Path) -> IoResult<Vec<Path>> {
                                 kind: io::InvalidInput,
                                 desc: "path was not valid UTF-16",
                                 detail: Some(format!("path was not valid UTF-16: {}", filename)),
+                                os_error: None,
                             })
                         }, // FIXME #12056: Convert the UCS-2 to invalid utf-8 instead of erroring
                     }
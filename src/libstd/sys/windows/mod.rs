@@ -105,6 +105,8 @@ pub fn decode_error(errno: i32) -> IoError {
         }
         libc::WSAENOTCONN => (io::NotConnected, "not connected"),
         libc::WSAECONNABORTED => (io::ConnectionAborted, "connection aborted"),
+        libc::WSAENETUNREACH => (io::NetworkUnreachable, "network unreachable"),
+        libc::WSAEHOSTUNREACH => (io::HostUnreachable, "host unreachable"),
         libc::WSAEADDRNOTAVAIL => (io::ConnectionRefused, "address not available"),
         libc::WSAEADDRINUSE => (io::ConnectionRefused, "address in use"),
         libc::ERROR_BROKEN_PIPE => (io::EndOfFile, "the pipe has ended"),
@@ -128,7 +130,7 @@ pub fn decode_error(errno: i32) -> IoError {
 
         _ => (io::OtherIoError, "unknown error")
     };
-    IoError { kind: kind, desc: desc, detail: None }
+    IoError { kind: kind, desc: desc, detail: None, os_error: Some(errno) }
 }
 
 pub fn decode_error_detailed(errno: i32) -> IoError {
@@ -179,6 +181,7 @@ pub fn unimpl() -> IoError {
         kind: io::IoUnavailable,
         desc: "operation is not implemented",
         detail: None,
+        os_error: None,
     }
 }
 
@@ -192,7 +195,8 @@ pub fn to_utf16(s: Option<&str>) -> IoResult<Vec<u16>> {
         None => Err(IoError {
             kind: io::InvalidInput,
             desc: "valid unicode input required",
-            detail: None
+            detail: None,
+            os_error: None
         })
     }
 }
@@ -199,6 +199,7 @@ fn epipe() -> IoError {
         kind: io::EndOfFile,
         desc: "the pipe has ended",
         detail: None,
+        os_error: None,
     }
 }
 
@@ -265,6 +266,10 @@ impl UnixStream {
         None
     }
 
+    pub fn connect_abstract(_name: &[u8]) -> IoResult<UnixStream> {
+        Err(sys_common::unimpl())
+    }
+
     pub fn connect(addr: &CString, timeout: Option<u64>) -> IoResult<UnixStream> {
         let addr = try!(to_utf16(addr.as_str()));
         let start = timer::now();
@@ -479,6 +484,7 @@ impl UnixStream {
                                 kind: io::ShortWrite(amt),
                                 desc: "short write during write",
                                 detail: None,
+                                os_error: None,
                             })
                         } else {
                             Err(sys_common::timeout("write timed out"))
@@ -560,6 +566,10 @@ pub struct UnixListener {
 }
 
 impl UnixListener {
+    pub fn bind_abstract(_name: &[u8]) -> IoResult<UnixListener> {
+        Err(sys_common::unimpl())
+    }
+
     pub fn bind(addr: &CString) -> IoResult<UnixListener> {
         // Although we technically don't need the pipe until much later, we
         // create the initial handle up front to test the validity of the name
@@ -81,6 +81,7 @@ impl Process {
                         kind: io::InvalidInput,
                         desc: "no process to kill",
                         detail: None,
+                        os_error: None,
                     })
                 } else {
                     Ok(())
@@ -94,6 +95,7 @@ impl Process {
                 kind: io::IoUnavailable,
                 desc: "unsupported signal on windows",
                 detail: None,
+                os_error: None,
             })
         };
         let _ = libc::CloseHandle(handle);
@@ -130,6 +132,7 @@ impl Process {
                 kind: io::IoUnavailable,
                 desc: "unsupported gid/uid requested on windows",
                 detail: None,
+                os_error: None,
             })
         }
 
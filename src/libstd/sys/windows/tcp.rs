@@ -67,6 +67,18 @@ impl TcpListener {
         }
     }
 
+    pub fn bind_device(_addr: ip::SocketAddr, _iface: &str) -> IoResult<TcpListener> {
+        Err(sys_common::unimpl())
+    }
+
+    pub fn bind_reuse_port(_addr: ip::SocketAddr) -> IoResult<TcpListener> {
+        Err(sys_common::unimpl())
+    }
+
+    pub fn new(sock: sock_t) -> TcpListener {
+        TcpListener { sock: sock }
+    }
+
     pub fn socket(&self) -> sock_t { self.sock }
 
     pub fn listen(self, backlog: int) -> IoResult<TcpAcceptor> {
@@ -97,6 +109,10 @@ impl TcpListener {
     pub fn socket_name(&mut self) -> IoResult<ip::SocketAddr> {
         sockname(self.socket(), libc::getsockname)
     }
+
+    pub fn set_defer_accept(&mut self, _seconds: Option<uint>) -> IoResult<()> {
+        Err(sys_common::unimpl())
+    }
 }
 
 impl Drop for TcpListener {
@@ -194,6 +210,30 @@ impl TcpAcceptor {
         Err(eof())
     }
 
+    pub fn try_accept(&mut self) -> IoResult<Option<TcpStream>> {
+        if self.inner.closed.load(atomic::SeqCst) {
+            return Err(eof())
+        }
+        match unsafe {
+            libc::accept(self.socket(), ptr::null_mut(), ptr::null_mut())
+        } {
+            -1 if wouldblock() => Ok(None),
+            -1 => Err(last_net_error()),
+
+            // As in accept, the new socket needs to be detached from our
+            // event and put back into blocking mode
+            socket => {
+                let stream = TcpStream::new(socket);
+                let ret = unsafe {
+                    c::WSAEventSelect(socket, self.inner.accept.handle(), 0)
+                };
+                if ret != 0 { return Err(last_net_error()) }
+                try!(set_nonblocking(socket, false));
+                Ok(Some(stream))
+            }
+        }
+    }
+
     pub fn socket_name(&mut self) -> IoResult<ip::SocketAddr> {
         sockname(self.socket(), libc::getsockname)
     }
@@ -202,6 +242,14 @@ impl TcpAcceptor {
         self.deadline = timeout.map(|a| timer::now() + a).unwrap_or(0);
     }
 
+    // Errors from accept() which leave the listening socket as it was, and
+    // which may well not happen again on the next call.
+    pub fn is_transient_error(errno: i32) -> bool {
+        use libc::consts::os::c95::WSAENOBUFS;
+        errno == libc::WSAEMFILE || errno == WSAENOBUFS ||
+            errno == libc::WSAECONNRESET || errno == libc::WSAECONNABORTED
+    }
+
     pub fn close_accept(&mut self) -> IoResult<()> {
         self.inner.closed.store(true, atomic::SeqCst);
         let ret = unsafe { c::WSASetEvent(self.inner.abort.handle()) };
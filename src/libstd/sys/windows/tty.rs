@@ -43,6 +43,7 @@ fn invalid_encoding() -> IoError {
         kind: io::InvalidInput,
         desc: "text was not valid unicode",
         detail: None,
+        os_error: None,
     }
 }
 
@@ -82,6 +83,7 @@ impl TTY {
                 kind: io::MismatchedFileTypeForOperation,
                 desc: "invalid handle provided to function",
                 detail: None,
+                os_error: None,
             })
         }
     }